@@ -31,6 +31,52 @@ impl From<&str> for Platform {
     }
 }
 
+#[derive(Debug, PartialEq, Default, Clone, Copy, Display, Serialize, Deserialize)]
+pub enum Channel {
+    #[default]
+    #[display(fmt = "experimental")]
+    /// Builds published every few days
+    Experimental,
+    #[display(fmt = "stable")]
+    /// Numbered releases like 0.G
+    Stable,
+}
+
+impl ::core::str::FromStr for Channel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "experimental" => Ok(Channel::Experimental),
+            "stable" => Ok(Channel::Stable),
+            _ => Err(anyhow::anyhow!(
+                "Unknown channel {}, expected experimental or stable",
+                s
+            )),
+        }
+    }
+}
+
+impl From<&str> for Channel {
+    fn from(release_tag: &str) -> Self {
+        if release_tag.to_lowercase().contains("experimental") {
+            Channel::Experimental
+        } else {
+            Channel::Stable
+        }
+    }
+}
+
+impl From<&Release> for Channel {
+    fn from(release: &Release) -> Self {
+        if release.prerelease {
+            Channel::Experimental
+        } else {
+            Channel::from(release.tag_name.as_str())
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Default, Clone, Display, Serialize, Deserialize)]
 pub enum TilesEdition {
     #[default]
@@ -124,6 +170,46 @@ pub struct Asset {
     pub game_edition_directory_path: GameEditionDirectoryPath,
 }
 
+/// Refers to an installed game the way the CLI does, by release tag and
+/// edition.
+#[derive(Debug, PartialEq, Default, Clone, Display, Serialize, Deserialize)]
+#[display(fmt = "{} ({})", tag, edition)]
+pub struct InstallationId {
+    pub tag: String,
+    pub edition: Edition,
+}
+
+impl InstallationId {
+    pub fn new(tag: &str, edition: &Edition) -> Self {
+        Self {
+            tag: tag.to_string(),
+            edition: edition.clone(),
+        }
+    }
+
+    pub fn matches(&self, asset: &Asset) -> bool {
+        self.tag == asset.tag && self.edition == asset.edition
+    }
+}
+
+/// Written as `TAG/EDITION`, or just `TAG` for the default edition.
+impl ::core::str::FromStr for InstallationId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.rsplit_once('/') {
+            Some((tag, edition)) => InstallationId::new(tag, &Edition::from_str(edition)?),
+            None => InstallationId::new(s, &Edition::default()),
+        })
+    }
+}
+
+impl From<&Asset> for InstallationId {
+    fn from(asset: &Asset) -> Self {
+        InstallationId::new(&asset.tag, &asset.edition)
+    }
+}
+
 #[derive(Debug, PartialEq, Default, Deref, Serialize, Deserialize)]
 pub struct DateTimePublished(Option<DateTime<Utc>>);
 
@@ -147,13 +233,19 @@ pub struct ReleaseRawAsset {
     pub name: String,
     pub url: String,
     pub browser_download_url: String,
+    /// Size of the archive in bytes.
+    #[serde(default)]
+    pub size: u64,
 }
 
 #[derive(Debug, PartialEq, Default, Serialize, Deserialize)]
 pub struct Release {
     pub name: String,
     pub tag_name: String,
-    pub body: String,
+    /// Missing for releases published without any description.
+    pub body: Option<String>,
+    #[serde(default)]
+    pub prerelease: bool,
     pub published_at: DateTimePublished,
     pub url: String,
     pub assets: Vec<ReleaseRawAsset>,
@@ -251,6 +343,151 @@ impl Default for UpstreamRepository {
     }
 }
 
+/// Amount of bytes, written as plain bytes or with a `K`, `M` or `G` suffix
+/// (powers of 1024) like `500M`.
+#[derive(Debug, PartialEq, PartialOrd, Default, Clone, Copy, Deref, Serialize, Deserialize)]
+pub struct ByteSize(u64);
+
+impl ByteSize {
+    pub fn new(bytes: u64) -> Self {
+        Self(bytes)
+    }
+}
+
+impl ::core::str::FromStr for ByteSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let unit_start = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (amount, unit) = s.split_at(unit_start);
+        let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+            "" | "b" => 1,
+            "k" | "kb" | "kib" => 1024,
+            "m" | "mb" | "mib" => 1024 * 1024,
+            "g" | "gb" | "gib" => 1024 * 1024 * 1024,
+            _ => return Err(anyhow::anyhow!("Unknown size unit in {}", s)),
+        };
+
+        amount
+            .parse::<u64>()?
+            .checked_mul(multiplier)
+            .map(ByteSize)
+            .ok_or_else(|| anyhow::anyhow!("{} is too large a size", s))
+    }
+}
+
+/// When downloaded archives get cleaned up on their own. Unset limits mean
+/// archives are kept forever.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ArchiveCachePolicy {
+    /// Keep only this many of the most recently downloaded archives.
+    pub keep_last: Option<usize>,
+    /// Remove the oldest archives until all of them fit in this size.
+    pub max_total_size: Option<ByteSize>,
+    /// Remove an archive as soon as it has been installed.
+    pub delete_after_install: bool,
+}
+
+/// How many installations of each edition and channel are kept around.
+/// Pinned, favorite and default installations are always kept.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct InstallationRetentionPolicy {
+    /// Keep only this many installations with the newest release tags.
+    pub keep_newest: Option<usize>,
+    /// Prune right after installing or updating.
+    pub prune_after_install: bool,
+}
+
+/// Whether launches get a user directory of ours through `--userdir`, so
+/// saves and configuration outlive installations.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct UserDirectoryPolicy {
+    pub enabled: bool,
+    /// Profile of installations that weren't assigned one, shared by all of
+    /// them.
+    pub default_profile: String,
+}
+
+impl Default for UserDirectoryPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            default_profile: "default".to_string(),
+        }
+    }
+}
+
+/// Gives an installation its own user directory profile instead of the
+/// default one.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct UserDirectoryAssignment {
+    pub installation: InstallationId,
+    pub profile: String,
+}
+
+/// How many save backups are kept of each world, or of a whole `save/`
+/// directory, before rotating out the oldest, and when they are made
+/// without asking.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BackupPolicy {
+    /// Zero keeps every backup.
+    pub keep: usize,
+    /// Back up the worlds `launch` is about to use.
+    pub before_launch: bool,
+    /// Back up the worlds of an installation right before update, migrate,
+    /// uninstall or an overwriting install changes it.
+    pub before_changes: bool,
+}
+
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        Self {
+            keep: 5,
+            before_launch: false,
+            before_changes: true,
+        }
+    }
+}
+
+/// What `launch` does when the build is older than the one that last wrote
+/// a world it may load.
+#[derive(Debug, PartialEq, Default, Clone, Copy, Serialize, Deserialize)]
+pub enum DowngradeProtection {
+    /// Warn and ask before launching.
+    #[default]
+    Confirm,
+    /// Warn, back up the affected worlds and launch.
+    Backup,
+    Off,
+}
+
+/// The installation that last wrote a world, recorded after each launch.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct WorldUsage {
+    /// Installation or user directory holding the world's `save/`.
+    pub user_data_path: PathBuf,
+    pub world: String,
+    pub installation: InstallationId,
+    /// Newest `# version` header of the world's character saves.
+    #[serde(default)]
+    pub save_version: Option<u32>,
+}
+
+/// Which releases of an edition `update` and `latest` resolve to.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct EditionSubscription {
+    pub edition: Edition,
+    pub channel: Channel,
+    /// Stay on this release tag no matter what the channel publishes.
+    #[serde(default)]
+    pub pinned_tag: Option<String>,
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
 pub struct Settings {
     pub upstream_repository: UpstreamRepository,
@@ -258,6 +495,37 @@ pub struct Settings {
     pub decompressed_game_directory_path: DecompressedGameDirectoryPath,
     pub launcher_name: LauncherName,
     pub installed_games: ReleaseAssets,
+    #[serde(default)]
+    pub archive_cache_policy: ArchiveCachePolicy,
+    /// Hardlink files that are identical between installations to a shared
+    /// content store instead of keeping a copy per installation. Linked files
+    /// are made read-only since a change to one would change it in every
+    /// installation, so edit shipped mods or tilesets in a copy instead.
+    #[serde(default)]
+    pub deduplicate_installations: bool,
+    #[serde(default)]
+    pub subscriptions: Vec<EditionSubscription>,
+    #[serde(default)]
+    pub installation_retention_policy: InstallationRetentionPolicy,
+    #[serde(default)]
+    pub favorite_installations: Vec<InstallationId>,
+    /// Launched when no release tag is given.
+    #[serde(default)]
+    pub default_installation: Option<InstallationId>,
+    /// Move removed installations and leftovers to the trash instead of
+    /// deleting them.
+    #[serde(default)]
+    pub move_to_trash: bool,
+    #[serde(default)]
+    pub user_directory_policy: UserDirectoryPolicy,
+    #[serde(default)]
+    pub user_directory_assignments: Vec<UserDirectoryAssignment>,
+    #[serde(default)]
+    pub backup_policy: BackupPolicy,
+    #[serde(default)]
+    pub downgrade_protection: DowngradeProtection,
+    #[serde(default)]
+    pub world_usages: Vec<WorldUsage>,
 }
 
 impl Settings {
@@ -276,6 +544,18 @@ impl Settings {
             ),
             launcher_name: LauncherName::new(launcher_name),
             installed_games: ReleaseAssets::new(installed_games),
+            archive_cache_policy: ArchiveCachePolicy::default(),
+            deduplicate_installations: false,
+            subscriptions: Vec::new(),
+            installation_retention_policy: InstallationRetentionPolicy::default(),
+            favorite_installations: Vec::new(),
+            default_installation: None,
+            move_to_trash: false,
+            user_directory_policy: UserDirectoryPolicy::default(),
+            user_directory_assignments: Vec::new(),
+            backup_policy: BackupPolicy::default(),
+            downgrade_protection: DowngradeProtection::default(),
+            world_usages: Vec::new(),
         }
     }
 }
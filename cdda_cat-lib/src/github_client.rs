@@ -5,6 +5,7 @@ use cdda_cat_data::entities::Release;
 #[async_trait]
 pub trait RepositoryReleaseClient {
     async fn get_by_tag(&self, tag: &str) -> Result<Release, Error>;
+    async fn get_recent(&self) -> Result<Vec<Release>, Error>;
 }
 
 pub struct GithubClient {
@@ -13,6 +14,8 @@ pub struct GithubClient {
 }
 
 const API_ROOT: &str = "https://api.github.com/repos";
+/// Experimentals come out almost daily, so this reaches back a few months.
+const RECENT_RELEASES_COUNT: u32 = 100;
 impl GithubClient {
     pub fn new(owner_name: &str, repo_name: &str) -> Self {
         Self {
@@ -41,4 +44,20 @@ impl RepositoryReleaseClient for GithubClient {
             .json::<Release>()
             .await?)
     }
+
+    async fn get_recent(&self) -> Result<Vec<Release>, Error> {
+        let client = reqwest::Client::builder()
+            .user_agent(APP_USER_AGENT)
+            .build()?;
+
+        Ok(client
+            .get(format!(
+                "{}/{}/{}/releases?per_page={}",
+                API_ROOT, self.owner_name, self.repo_name, RECENT_RELEASES_COUNT
+            ))
+            .send()
+            .await?
+            .json::<Vec<Release>>()
+            .await?)
+    }
 }
@@ -1,9 +1,17 @@
 use crate::github_client::{GithubClient, RepositoryReleaseClient};
+use crate::tag_ordering::compare_tags;
 use anyhow::{Context, Error};
-use cdda_cat_data::entities::{DateTimePublished, Release, Settings};
+use cdda_cat_data::entities::{
+    Asset, Channel, DateTimePublished, Edition, EditionSubscription, GameEditionDirectoryPath,
+    InstallationId, Platform, Release, Settings, UserDirectoryAssignment, WorldUsage,
+};
 use derive_more::{Deref, DerefMut};
 use serde::{Deserialize, Serialize};
-use std::{fs, path::Path};
+use std::{
+    cmp::Ordering,
+    fs,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug, PartialEq, Default, Deref)]
 pub struct CDDARelease(Release);
@@ -12,6 +20,36 @@ impl CDDARelease {
     pub async fn fetch_by_tag(client: GithubClient, tag: &str) -> Result<CDDARelease, Error> {
         CDDARelease::try_from(client.get_by_tag(tag).await?)
     }
+
+    /// Most recent releases first.
+    pub async fn fetch_recent(client: GithubClient) -> Result<Vec<CDDARelease>, Error> {
+        client
+            .get_recent()
+            .await?
+            .into_iter()
+            .map(CDDARelease::try_from)
+            .collect()
+    }
+
+    pub fn channel(&self) -> Channel {
+        Channel::from(&self.0)
+    }
+
+    /// The Linux build of `edition` published with this release, not yet
+    /// placed in any directory.
+    pub fn linux_asset(&self, edition: &Edition) -> Option<Asset> {
+        self.assets
+            .iter()
+            .map(|asset| Asset {
+                name: asset.name.to_string(),
+                tag: self.tag_name.to_string(),
+                platform: Platform::from(asset.name.as_str()),
+                edition: Edition::from(asset.name.as_str()),
+                url: asset.browser_download_url.to_string(),
+                game_edition_directory_path: GameEditionDirectoryPath::default(),
+            })
+            .find(|asset| asset.platform == Platform::Linux && &asset.edition == edition)
+    }
 }
 
 impl TryFrom<Release> for CDDARelease {
@@ -21,6 +59,7 @@ impl TryFrom<Release> for CDDARelease {
             name: release.name,
             tag_name: release.tag_name.to_string(),
             body: release.body,
+            prerelease: release.prerelease,
             published_at: DateTimePublished::new(*release.published_at),
             url: release.url.to_string(),
             assets: release.assets,
@@ -44,4 +83,387 @@ impl AppSettings {
         fs::write(settings_filepath, serde_json::to_string_pretty(self)?)
             .with_context(|| format!("Failed to write to {} file!", settings_filepath.display()))
     }
+
+    /// Installed Linux games of `edition` in `channel`, newest release tag
+    /// first.
+    pub fn installed_games_of(&self, edition: &Edition, channel: Channel) -> Vec<&Asset> {
+        let mut assets: Vec<&Asset> = self
+            .installed_games
+            .iter()
+            .filter(|asset| {
+                asset.platform == Platform::Linux
+                    && &asset.edition == edition
+                    && Channel::from(asset.tag.as_str()) == channel
+            })
+            .collect();
+        assets.sort_by(|a, b| compare_tags(&b.tag, &a.tag).unwrap_or(Ordering::Equal));
+        assets
+    }
+
+    /// The installed Linux game of `edition` that was installed last, of
+    /// whichever channel.
+    pub fn last_installed_game_of(&self, edition: &Edition) -> Option<&Asset> {
+        self.installed_games
+            .iter()
+            .rev()
+            .find(|asset| asset.platform == Platform::Linux && &asset.edition == edition)
+    }
+
+    /// The stored subscription of `edition`, or one following the channel of
+    /// its last installed game.
+    pub fn subscription_of(&self, edition: &Edition) -> EditionSubscription {
+        self.subscriptions
+            .iter()
+            .find(|subscription| &subscription.edition == edition)
+            .cloned()
+            .unwrap_or_else(|| EditionSubscription {
+                edition: edition.clone(),
+                channel: self
+                    .last_installed_game_of(edition)
+                    .map(|asset| Channel::from(asset.tag.as_str()))
+                    .unwrap_or_default(),
+                pinned_tag: None,
+            })
+    }
+
+    /// Whether pruning has to leave `asset` alone.
+    pub fn is_protected(&self, asset: &Asset) -> bool {
+        self.subscription_of(&asset.edition).pinned_tag.as_deref() == Some(asset.tag.as_str())
+            || self
+                .favorite_installations
+                .iter()
+                .any(|favorite| favorite.matches(asset))
+            || self
+                .default_installation
+                .as_ref()
+                .is_some_and(|default| default.matches(asset))
+    }
+
+    /// Installations beyond the `keep_newest` newest ones of each edition
+    /// and channel, leaving out protected ones.
+    pub fn select_installations_to_prune(&self, keep_newest: usize) -> Vec<Asset> {
+        let mut editions: Vec<(&Edition, Channel)> = Vec::new();
+        for asset in self.installed_games.iter() {
+            let edition = (&asset.edition, Channel::from(asset.tag.as_str()));
+            if !editions.contains(&edition) {
+                editions.push(edition);
+            }
+        }
+
+        editions
+            .into_iter()
+            .flat_map(|(edition, channel)| {
+                self.installed_games_of(edition, channel)
+                    .into_iter()
+                    .skip(keep_newest)
+            })
+            .filter(|asset| !self.is_protected(asset))
+            .cloned()
+            .collect()
+    }
+
+    /// User directory profile `asset` is launched with, `None` when launches
+    /// don't get one.
+    pub fn user_directory_profile_of(&self, asset: &Asset) -> Option<String> {
+        if !self.user_directory_policy.enabled {
+            return None;
+        }
+
+        Some(
+            self.user_directory_assignments
+                .iter()
+                .find(|assignment| assignment.installation.matches(asset))
+                .map(|assignment| assignment.profile.to_string())
+                .unwrap_or_else(|| self.user_directory_policy.default_profile.to_string()),
+        )
+    }
+
+    pub fn find_installation(&self, installation: &InstallationId) -> Option<&Asset> {
+        self.installed_games
+            .iter()
+            .find(|asset| installation.matches(asset))
+    }
+
+    /// Game directory of `asset`, the one holding its launcher and user data.
+    pub fn installation_path_of(&self, asset: &Asset) -> PathBuf {
+        asset
+            .game_edition_directory_path
+            .join(self.decompressed_game_directory_path.as_path())
+    }
+
+    pub fn world_usage_of(&self, user_data_path: &Path, world: &str) -> Option<&WorldUsage> {
+        self.world_usages
+            .iter()
+            .find(|usage| usage.user_data_path == user_data_path && usage.world == world)
+    }
+
+    pub fn record_world_usage(&mut self, usage: WorldUsage) {
+        self.world_usages.retain(|existing| {
+            existing.user_data_path != usage.user_data_path || existing.world != usage.world
+        });
+        self.world_usages.push(usage);
+    }
+
+    /// Newest save format `tag` was seen writing.
+    pub fn save_version_written_by(&self, tag: &str) -> Option<u32> {
+        self.world_usages
+            .iter()
+            .filter(|usage| usage.installation.tag == tag)
+            .filter_map(|usage| usage.save_version)
+            .max()
+    }
+
+    /// Whether `tag` is older than the build that last wrote a world. A save
+    /// format newer than what `tag` was seen writing always is. Otherwise
+    /// release tags tell within a channel, and the save formats both builds
+    /// wrote across channels. `None` when nothing tells, like for a build of
+    /// another channel that was never seen saving.
+    pub fn is_downgrade(
+        &self,
+        tag: &str,
+        usage: Option<&WorldUsage>,
+        save_version: Option<u32>,
+    ) -> Option<bool> {
+        let written_by_tag = self.save_version_written_by(tag);
+        if let (Some(written), Some(current)) = (written_by_tag, save_version) {
+            if written < current {
+                return Some(true);
+            }
+        }
+        let usage = match usage {
+            Some(usage) if usage.installation.tag != tag => usage,
+            _ => return Some(false),
+        };
+        if let Some(ordering) = compare_tags(tag, &usage.installation.tag) {
+            return Some(ordering == Ordering::Less);
+        }
+
+        let written_by_usage = self
+            .save_version_written_by(&usage.installation.tag)
+            .or(usage.save_version);
+        match (written_by_tag, written_by_usage) {
+            (Some(written), Some(written_by_usage)) if written != written_by_usage => {
+                Some(written < written_by_usage)
+            }
+            _ => None,
+        }
+    }
+
+    /// Gives `to` the user directory profile assigned to `from`, unless `to`
+    /// has one of its own. Returns the inherited profile.
+    pub fn inherit_user_directory(&mut self, from: &Asset, to: &Asset) -> Option<String> {
+        if self
+            .user_directory_assignments
+            .iter()
+            .any(|assignment| assignment.installation.matches(to))
+        {
+            return None;
+        }
+
+        let profile = self
+            .user_directory_assignments
+            .iter()
+            .find(|assignment| assignment.installation.matches(from))?
+            .profile
+            .to_string();
+        self.user_directory_assignments
+            .push(UserDirectoryAssignment {
+                installation: InstallationId::from(to),
+                profile: profile.to_string(),
+            });
+
+        Some(profile)
+    }
+
+    /// Drops `asset` from the installed games along with every setting that
+    /// refers to it. World usages stay, they still tell which build last
+    /// wrote a world once that build is gone.
+    pub fn forget_installation(&mut self, asset: &Asset) {
+        self.installed_games.retain(|installed| installed != asset);
+        self.favorite_installations
+            .retain(|favorite| !favorite.matches(asset));
+        if self
+            .default_installation
+            .as_ref()
+            .is_some_and(|default| default.matches(asset))
+        {
+            self.default_installation = None;
+        }
+        self.user_directory_assignments
+            .retain(|assignment| !assignment.installation.matches(asset));
+        for subscription in self.subscriptions.iter_mut() {
+            if subscription.edition == asset.edition
+                && subscription.pinned_tag.as_deref() == Some(asset.tag.as_str())
+            {
+                subscription.pinned_tag = None;
+            }
+        }
+    }
+
+    pub fn set_subscription(&mut self, subscription: EditionSubscription) {
+        self.subscriptions
+            .retain(|existing| existing.edition != subscription.edition);
+        self.subscriptions.push(subscription);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings_with(tags: &[&str]) -> AppSettings {
+        let mut settings = AppSettings::default();
+        for tag in tags {
+            settings.installed_games.push(Asset {
+                tag: tag.to_string(),
+                ..Asset::default()
+            });
+        }
+        settings
+    }
+
+    fn tags_of(assets: &[Asset]) -> Vec<&str> {
+        assets.iter().map(|asset| asset.tag.as_str()).collect()
+    }
+
+    #[test]
+    fn prunes_each_channel_on_its_own() {
+        let settings = settings_with(&[
+            "0.H",
+            "cdda-experimental-2024-10-17-0123",
+            "0.G",
+            "cdda-experimental-2024-9-30-1000",
+        ]);
+
+        assert_eq!(
+            tags_of(&settings.select_installations_to_prune(1)),
+            ["0.G", "cdda-experimental-2024-9-30-1000"]
+        );
+        assert_eq!(
+            settings
+                .installed_games_of(&Edition::default(), Channel::Stable)
+                .first()
+                .map(|asset| asset.tag.as_str()),
+            Some("0.H")
+        );
+    }
+
+    #[test]
+    fn follows_the_channel_installed_last() {
+        let settings = settings_with(&["cdda-experimental-2024-10-17-0123", "0.G"]);
+        assert_eq!(
+            settings.subscription_of(&Edition::default()).channel,
+            Channel::Stable
+        );
+    }
+
+    fn usage_by(tag: &str, save_version: Option<u32>) -> WorldUsage {
+        WorldUsage {
+            user_data_path: PathBuf::from("/userdirs/default"),
+            world: "World1".to_string(),
+            installation: InstallationId::new(tag, &Edition::default()),
+            save_version,
+        }
+    }
+
+    #[test]
+    fn orders_builds_of_a_channel_by_tag() {
+        let settings = AppSettings::default();
+        let usage = usage_by("0.H", None);
+
+        assert_eq!(settings.is_downgrade("0.G", Some(&usage), None), Some(true));
+        assert_eq!(
+            settings.is_downgrade("0.H", Some(&usage), None),
+            Some(false)
+        );
+        assert_eq!(
+            settings.is_downgrade("0.I", Some(&usage), None),
+            Some(false)
+        );
+        assert_eq!(settings.is_downgrade("0.G", None, None), Some(false));
+    }
+
+    #[test]
+    fn orders_builds_across_channels_by_the_save_formats_they_wrote() {
+        let experimental = "cdda-experimental-2024-10-17-0123";
+        let mut settings = AppSettings::default();
+        let usage = usage_by(experimental, Some(35));
+
+        // Nothing tells a stable build apart from an experimental one by age.
+        assert_eq!(settings.is_downgrade("0.H", Some(&usage), Some(35)), None);
+
+        settings.world_usages.push(usage_by("0.H", Some(33)));
+        assert_eq!(
+            settings.is_downgrade("0.H", Some(&usage), Some(35)),
+            Some(true)
+        );
+        // Even with a world that still has the older format.
+        assert_eq!(
+            settings.is_downgrade("0.H", Some(&usage), Some(33)),
+            Some(true)
+        );
+
+        settings.world_usages = vec![usage_by("0.I", Some(36))];
+        assert_eq!(
+            settings.is_downgrade("0.I", Some(&usage), Some(35)),
+            Some(false)
+        );
+        settings.world_usages = vec![usage_by("0.I", Some(35))];
+        assert_eq!(settings.is_downgrade("0.I", Some(&usage), Some(35)), None);
+    }
+
+    #[test]
+    fn updated_installations_keep_their_user_directory() {
+        let mut settings = settings_with(&["0.G", "0.H"]);
+        let (old, new) = (
+            settings.installed_games[0].clone(),
+            settings.installed_games[1].clone(),
+        );
+        settings.user_directory_assignments = vec![UserDirectoryAssignment {
+            installation: InstallationId::from(&old),
+            profile: "mine".to_string(),
+        }];
+
+        assert_eq!(
+            settings.inherit_user_directory(&old, &new),
+            Some("mine".to_string())
+        );
+        settings.forget_installation(&old);
+
+        assert_eq!(
+            settings.user_directory_profile_of(&new),
+            Some("mine".to_string())
+        );
+        assert_eq!(settings.inherit_user_directory(&new, &new), None);
+    }
+
+    #[test]
+    fn forgets_every_reference_to_a_removed_installation() {
+        let mut settings = settings_with(&["0.G", "0.H"]);
+        let removed = settings.installed_games[0].clone();
+        let kept = InstallationId::from(&settings.installed_games[1]);
+        let removed_id = InstallationId::from(&removed);
+        settings.favorite_installations = vec![removed_id.clone(), kept.clone()];
+        settings.default_installation = Some(removed_id.clone());
+        settings.user_directory_assignments = vec![UserDirectoryAssignment {
+            installation: removed_id,
+            profile: "old".to_string(),
+        }];
+        settings.set_subscription(EditionSubscription {
+            edition: Edition::default(),
+            channel: Channel::Stable,
+            pinned_tag: Some("0.G".to_string()),
+        });
+
+        settings.forget_installation(&removed);
+
+        assert_eq!(tags_of(&settings.installed_games), ["0.H"]);
+        assert_eq!(settings.favorite_installations, [kept]);
+        assert_eq!(settings.default_installation, None);
+        assert!(settings.user_directory_assignments.is_empty());
+        assert_eq!(
+            settings.subscription_of(&Edition::default()).pinned_tag,
+            None
+        );
+    }
 }
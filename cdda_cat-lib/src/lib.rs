@@ -1,2 +1,3 @@
 pub mod github_client;
 pub mod installation_manager;
+pub mod tag_ordering;
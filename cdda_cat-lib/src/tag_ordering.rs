@@ -0,0 +1,112 @@
+use cdda_cat_data::entities::Channel;
+use std::cmp::Ordering;
+
+/// Orders release tags of the same channel so that numbers inside them
+/// compare by value, e.g. `0.F-3` comes after `0.F-2` and
+/// `...-2024-10-17-0123` after `...-2024-9-30-1000`. Stable and experimental
+/// tags are numbered independently, so tags of different channels don't
+/// compare at all.
+pub fn compare_tags(a: &str, b: &str) -> Option<Ordering> {
+    if Channel::from(a) != Channel::from(b) {
+        return None;
+    }
+
+    Some(compare_chunks(a, b))
+}
+
+fn compare_chunks(a: &str, b: &str) -> Ordering {
+    let mut a_chunks = TagChunks::new(a);
+    let mut b_chunks = TagChunks::new(b);
+    loop {
+        match (a_chunks.next(), b_chunks.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_chunk), Some(b_chunk)) => {
+                let ordering = match (a_chunk.parse::<u64>(), b_chunk.parse::<u64>()) {
+                    (Ok(a_number), Ok(b_number)) => a_number.cmp(&b_number),
+                    _ => a_chunk.to_lowercase().cmp(&b_chunk.to_lowercase()),
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+/// Splits a tag into runs of digits and runs of everything else.
+struct TagChunks<'a> {
+    rest: &'a str,
+}
+
+impl<'a> TagChunks<'a> {
+    fn new(tag: &'a str) -> Self {
+        Self { rest: tag }
+    }
+}
+
+impl<'a> Iterator for TagChunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = self.rest.chars().next()?;
+        let end = self
+            .rest
+            .find(|c: char| c.is_ascii_digit() != first.is_ascii_digit())
+            .unwrap_or(self.rest.len());
+        let (chunk, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Some(chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_numbers_by_value() {
+        assert_eq!(compare_tags("0.F-3", "0.F-2"), Some(Ordering::Greater));
+        assert_eq!(compare_tags("0.F", "0.F-2"), Some(Ordering::Less));
+        assert_eq!(compare_tags("0.G", "0.F-3"), Some(Ordering::Greater));
+        assert_eq!(compare_tags("0.H", "0.h"), Some(Ordering::Equal));
+    }
+
+    #[test]
+    fn compares_dates_regardless_of_padding() {
+        assert_eq!(
+            compare_tags(
+                "cdda-experimental-2024-10-17-0123",
+                "cdda-experimental-2024-9-30-1000"
+            ),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            compare_tags(
+                "cdda-experimental-2024-09-30-1000",
+                "cdda-experimental-2024-9-30-1000"
+            ),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            compare_tags(
+                "cdda-experimental-2024-10-17-0123",
+                "cdda-experimental-2024-10-17-0045"
+            ),
+            Some(Ordering::Greater)
+        );
+    }
+
+    #[test]
+    fn leaves_tags_of_different_channels_unordered() {
+        assert_eq!(
+            compare_tags("0.H", "cdda-experimental-2020-01-01-0000"),
+            None
+        );
+        assert_eq!(
+            compare_tags("cdda-experimental-2024-10-17-0123", "0.G"),
+            None
+        );
+    }
+}
@@ -1,2 +1,7 @@
+pub mod safe_extract;
 pub mod tar_gz;
+pub mod tar_xz;
+pub mod tar_zst;
+pub mod tarball;
 pub mod unpacker;
+pub mod zip;
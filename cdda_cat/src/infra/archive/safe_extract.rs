@@ -0,0 +1,711 @@
+use anyhow::{Context, Error};
+use derive_more::Display;
+use std::fs::{self, File, Permissions};
+use std::io::{self, Read};
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+
+use crate::infra::fs::content_store::{ContentStore, HashingWriter};
+
+use super::unpacker::strip_top_level_component;
+
+/// What an archive entry would become on disk, independent of archive format.
+#[derive(Debug, PartialEq, Clone)]
+pub enum EntryKind {
+    Directory,
+    File,
+    Symlink(PathBuf),
+    Hardlink(PathBuf),
+    /// Character/block devices, FIFOs and sockets.
+    SpecialFile,
+    /// Format bookkeeping entries like pax or GNU long name headers.
+    Metadata,
+    Unsupported(String),
+}
+
+#[derive(Debug, PartialEq, Clone, Display)]
+pub enum RejectReason {
+    #[display(fmt = "absolute path")]
+    AbsolutePath,
+    #[display(fmt = "path escapes installation directory with '..'")]
+    ParentTraversal,
+    #[display(fmt = "path goes through a symlink outside installation directory")]
+    ThroughOutsideSymlink,
+    #[display(
+        fmt = "link target {} points outside installation directory",
+        "_0.display()"
+    )]
+    LinkTargetOutside(PathBuf),
+    #[display(
+        fmt = "hardlink target {} is not an extracted regular file",
+        "_0.display()"
+    )]
+    HardlinkTargetMissing(PathBuf),
+    #[display(fmt = "device, FIFO or socket entries are not allowed")]
+    SpecialFile,
+    #[display(fmt = "unsupported entry type {}", _0)]
+    UnsupportedEntryType(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RejectedEntry {
+    pub path: PathBuf,
+    pub reason: RejectReason,
+}
+
+#[derive(Debug, Clone)]
+pub struct FailedEntry {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ExtractionReport {
+    pub extracted_entries: u64,
+    pub rejected_entries: Vec<RejectedEntry>,
+    /// Entries that couldn't be read from the archive or written to disk.
+    pub failed_entries: Vec<FailedEntry>,
+    /// Files replaced by links into the content store, and their total size.
+    pub deduplicated_entries: u64,
+    pub deduplicated_bytes: u64,
+}
+
+impl ExtractionReport {
+    pub fn print_rejected_entries(&self) {
+        if self.rejected_entries.is_empty() {
+            return;
+        }
+
+        eprintln!(
+            "Refused to extract {} unsafe archive entries:",
+            self.rejected_entries.len()
+        );
+        for rejected in &self.rejected_entries {
+            eprintln!("  {}: {}", rejected.path.display(), rejected.reason);
+        }
+    }
+
+    pub fn print_failed_entries(&self) {
+        if self.failed_entries.is_empty() {
+            return;
+        }
+
+        eprintln!(
+            "Failed to extract {} archive entries:",
+            self.failed_entries.len()
+        );
+        for failed in &self.failed_entries {
+            eprintln!("  {}: {}", failed.path.display(), failed.error);
+        }
+    }
+}
+
+enum Outcome {
+    Extracted,
+    Rejected(RejectReason),
+}
+
+/// Extracts archive entries while making sure nothing ends up outside of
+/// `destination_path`, whatever the archive claims.
+pub struct SafeExtractor {
+    destination_path: PathBuf,
+    canonical_destination_path: PathBuf,
+    /// Archive entry path and where we created it.
+    created_symlinks: Vec<(PathBuf, PathBuf)>,
+    content_store: Option<ContentStore>,
+    report: ExtractionReport,
+}
+
+impl SafeExtractor {
+    pub fn new(destination_path: &Path) -> Result<Self, Error> {
+        fs::create_dir_all(destination_path).with_context(|| {
+            format!(
+                "Failed to create installation directory: {}",
+                destination_path.display()
+            )
+        })?;
+        let canonical_destination_path = destination_path.canonicalize()?;
+
+        Ok(Self {
+            destination_path: destination_path.to_path_buf(),
+            canonical_destination_path,
+            created_symlinks: Vec::new(),
+            content_store: None,
+            report: ExtractionReport::default(),
+        })
+    }
+
+    /// Links extracted files into `content_store` instead of keeping copies of
+    /// files other installations already have.
+    pub fn with_content_store(mut self, content_store: Option<ContentStore>) -> Self {
+        self.content_store = content_store;
+        self
+    }
+
+    /// Extracts a single entry. Returns the path written to, or `None` when the
+    /// entry was skipped or rejected.
+    pub fn extract(
+        &mut self,
+        entry_path: &Path,
+        kind: EntryKind,
+        mode: Option<u32>,
+        contents: &mut dyn Read,
+    ) -> Result<Option<PathBuf>, Error> {
+        let relative_path = match relative_entry_path(entry_path) {
+            Ok(relative_path) => relative_path,
+            Err(reason) => return Ok(self.reject(entry_path, reason)),
+        };
+        let new_path = self.destination_path.join(&relative_path);
+
+        let outcome = match kind {
+            EntryKind::Metadata => return Ok(None),
+            EntryKind::SpecialFile => Outcome::Rejected(RejectReason::SpecialFile),
+            EntryKind::Unsupported(entry_type) => {
+                Outcome::Rejected(RejectReason::UnsupportedEntryType(entry_type))
+            }
+            // The archive's top level directory is our destination itself.
+            _ if relative_path.as_os_str().is_empty() => return Ok(None),
+            _ if !self.prepare_parent_directory(&new_path)? => {
+                Outcome::Rejected(RejectReason::ThroughOutsideSymlink)
+            }
+            EntryKind::Directory => {
+                if new_path.is_symlink() {
+                    fs::remove_file(&new_path)?;
+                }
+                fs::create_dir_all(&new_path)?;
+                fs::set_permissions(
+                    &new_path,
+                    Permissions::from_mode(mode.unwrap_or(0o755) & 0o777 | 0o700),
+                )?;
+                Outcome::Extracted
+            }
+            EntryKind::File => {
+                remove_existing_file(&new_path)?;
+                let mode = mode.unwrap_or(0o644) & 0o777 | 0o600;
+                match &self.content_store {
+                    Some(content_store) => {
+                        let mut writer = HashingWriter::new(File::create(&new_path)?);
+                        let size = io::copy(contents, &mut writer)?;
+                        let (file, digest) = writer.into_digest();
+                        file.set_permissions(Permissions::from_mode(mode))?;
+                        drop(file);
+                        if content_store.deduplicate(&new_path, &digest, mode)? {
+                            self.report.deduplicated_entries += 1;
+                            self.report.deduplicated_bytes += size;
+                        }
+                    }
+                    None => {
+                        let mut file = File::create(&new_path)?;
+                        io::copy(contents, &mut file)?;
+                        file.set_permissions(Permissions::from_mode(mode))?;
+                    }
+                }
+                Outcome::Extracted
+            }
+            EntryKind::Symlink(target) => {
+                match self.check_symlink_target(&relative_path, &target) {
+                    Ok(()) => {
+                        remove_existing_file(&new_path)?;
+                        symlink(&target, &new_path)?;
+                        self.created_symlinks
+                            .push((entry_path.to_path_buf(), new_path.clone()));
+                        Outcome::Extracted
+                    }
+                    Err(reason) => Outcome::Rejected(reason),
+                }
+            }
+            EntryKind::Hardlink(target) => match self.hardlink_target_path(&target)? {
+                Ok(target_path) => {
+                    remove_existing_file(&new_path)?;
+                    fs::hard_link(&target_path, &new_path)?;
+                    Outcome::Extracted
+                }
+                Err(reason) => Outcome::Rejected(reason),
+            },
+        };
+
+        match outcome {
+            Outcome::Extracted => {
+                self.report.extracted_entries += 1;
+                Ok(Some(new_path))
+            }
+            Outcome::Rejected(reason) => Ok(self.reject(entry_path, reason)),
+        }
+    }
+
+    /// Checks symlinks once every entry is on disk, since a later entry can
+    /// change where an earlier symlink resolves to.
+    pub fn finish(mut self) -> Result<ExtractionReport, Error> {
+        for (entry_path, symlink_path) in std::mem::take(&mut self.created_symlinks) {
+            let is_inside = match symlink_path.canonicalize() {
+                Ok(resolved_path) => resolved_path.starts_with(&self.canonical_destination_path),
+                // Dangling symlinks were already checked lexically.
+                Err(_) => true,
+            };
+            if !is_inside {
+                let target = fs::read_link(&symlink_path)?;
+                fs::remove_file(&symlink_path)?;
+                self.report.extracted_entries -= 1;
+                self.reject(&entry_path, RejectReason::LinkTargetOutside(target));
+            }
+        }
+
+        Ok(self.report)
+    }
+
+    /// Records an entry we couldn't read or write, so the caller can tell the
+    /// installation is incomplete.
+    pub fn fail(&mut self, entry_path: &Path, error: Error) {
+        self.report.failed_entries.push(FailedEntry {
+            path: entry_path.to_path_buf(),
+            error: format!("{:#}", error),
+        });
+    }
+
+    fn reject(&mut self, entry_path: &Path, reason: RejectReason) -> Option<PathBuf> {
+        self.report.rejected_entries.push(RejectedEntry {
+            path: entry_path.to_path_buf(),
+            reason,
+        });
+        None
+    }
+
+    /// Hardlink targets are archive paths too, and must point at a regular
+    /// file we already extracted.
+    fn hardlink_target_path(&self, target: &Path) -> Result<Result<PathBuf, RejectReason>, Error> {
+        let target_path = match relative_entry_path(target) {
+            Ok(relative_target) => self.destination_path.join(relative_target),
+            Err(_) => return Ok(Err(RejectReason::LinkTargetOutside(target.to_path_buf()))),
+        };
+        let is_extracted_file = fs::symlink_metadata(&target_path)
+            .map(|metadata| metadata.is_file())
+            .unwrap_or(false);
+        if !is_extracted_file || !self.is_inside_destination(&target_path)? {
+            return Ok(Err(RejectReason::HardlinkTargetMissing(
+                target.to_path_buf(),
+            )));
+        }
+
+        Ok(Ok(target_path))
+    }
+
+    /// Creates the parent directory of `new_path` and makes sure it really
+    /// lives inside the destination after following any symlinks.
+    fn prepare_parent_directory(&self, new_path: &Path) -> Result<bool, Error> {
+        match new_path.parent() {
+            Some(parent) => {
+                // Resolve what already exists first so we never create
+                // directories below a symlink pointing elsewhere.
+                let existing_ancestor = parent
+                    .ancestors()
+                    .find(|ancestor| ancestor.exists())
+                    .unwrap_or(parent);
+                if !existing_ancestor
+                    .canonicalize()?
+                    .starts_with(&self.canonical_destination_path)
+                {
+                    return Ok(false);
+                }
+                fs::create_dir_all(parent)?;
+                Ok(parent
+                    .canonicalize()?
+                    .starts_with(&self.canonical_destination_path))
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn is_inside_destination(&self, path: &Path) -> Result<bool, Error> {
+        let parent = match path.parent() {
+            Some(parent) if path.is_file() || path.is_symlink() => parent,
+            _ => path,
+        };
+        Ok(parent
+            .canonicalize()?
+            .starts_with(&self.canonical_destination_path))
+    }
+
+    /// Resolves `target` lexically from the symlink's own directory. Walking
+    /// back with `..` through another symlink is refused since we can't tell
+    /// where that ends up without following it.
+    fn check_symlink_target(
+        &self,
+        relative_link_path: &Path,
+        target: &Path,
+    ) -> Result<(), RejectReason> {
+        let mut resolved_path = relative_link_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        for component in target.components() {
+            match component {
+                Component::Normal(part) => resolved_path.push(part),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    let goes_through_symlink = resolved_path
+                        .ancestors()
+                        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+                        .any(|ancestor| self.destination_path.join(ancestor).is_symlink());
+                    if goes_through_symlink || !resolved_path.pop() {
+                        return Err(RejectReason::LinkTargetOutside(target.to_path_buf()));
+                    }
+                }
+                Component::RootDir | Component::Prefix(_) => {
+                    return Err(RejectReason::LinkTargetOutside(target.to_path_buf()))
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Turns an archive entry path into a path relative to the installation
+/// directory, refusing anything absolute or containing `..`.
+pub fn relative_entry_path(entry_path: &Path) -> Result<PathBuf, RejectReason> {
+    let mut relative_path = PathBuf::new();
+    for component in entry_path.components() {
+        match component {
+            Component::Normal(part) => relative_path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => return Err(RejectReason::ParentTraversal),
+            Component::RootDir | Component::Prefix(_) => return Err(RejectReason::AbsolutePath),
+        }
+    }
+
+    Ok(strip_top_level_component(&relative_path))
+}
+
+/// Existing files and symlinks are replaced rather than written through.
+fn remove_existing_file(path: &Path) -> Result<(), Error> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => Ok(fs::remove_dir_all(path)?),
+        Ok(_) => Ok(fs::remove_file(path)?),
+        Err(_) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::archive::{tarball, zip};
+    use crate::infra::test_directory::TestDirectory;
+    use cdda_cat_data::entities::ArchiveFilePath;
+    use std::io::{Cursor, Write};
+    use std::os::unix::fs::MetadataExt;
+    use tar::{EntryType, Header};
+
+    /// Tar entries with raw names, since `tar::Header::set_path` refuses the
+    /// paths these tests are about.
+    struct TarFixture {
+        builder: tar::Builder<Vec<u8>>,
+    }
+
+    impl TarFixture {
+        fn new() -> Self {
+            Self {
+                builder: tar::Builder::new(Vec::new()),
+            }
+        }
+
+        fn entry(mut self, path: &str, entry_type: EntryType, link: &str, data: &[u8]) -> Self {
+            let mut header = Header::new_old();
+            header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.as_old_mut().linkname[..link.len()].copy_from_slice(link.as_bytes());
+            header.set_entry_type(entry_type);
+            header.set_mode(0o644);
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            self.builder.append(&header, data).unwrap();
+            self
+        }
+
+        fn file(self, path: &str, data: &[u8]) -> Self {
+            self.entry(path, EntryType::Regular, "", data)
+        }
+
+        fn symlink(self, path: &str, target: &str) -> Self {
+            self.entry(path, EntryType::Symlink, target, b"")
+        }
+
+        fn hardlink(self, path: &str, target: &str) -> Self {
+            self.entry(path, EntryType::Link, target, b"")
+        }
+
+        fn unpack(self, destination_path: &Path) -> ExtractionReport {
+            let bytes = self.builder.into_inner().unwrap();
+            tarball::unpack(
+                Cursor::new(bytes),
+                SafeExtractor::new(destination_path).unwrap(),
+            )
+            .unwrap()
+        }
+    }
+
+    fn rejection_of<'a>(report: &'a ExtractionReport, path: &str) -> Option<&'a RejectReason> {
+        report
+            .rejected_entries
+            .iter()
+            .find(|rejected| rejected.path == Path::new(path))
+            .map(|rejected| &rejected.reason)
+    }
+
+    #[test]
+    fn entry_paths_lose_the_top_level_directory() {
+        assert_eq!(
+            relative_entry_path(Path::new("cataclysmdda-0.G/data/json/a.json")),
+            Ok(PathBuf::from("data/json/a.json"))
+        );
+        assert_eq!(
+            relative_entry_path(Path::new("./CDDA/./data")),
+            Ok(PathBuf::from("data"))
+        );
+    }
+
+    #[test]
+    fn entry_paths_must_not_traverse_or_be_absolute() {
+        for entry_path in ["../evil", "CDDA/../evil", "CDDA/data/../../../evil"] {
+            assert_eq!(
+                relative_entry_path(Path::new(entry_path)),
+                Err(RejectReason::ParentTraversal),
+                "{}",
+                entry_path
+            );
+        }
+        for entry_path in ["/etc/passwd", "/CDDA/data"] {
+            assert_eq!(
+                relative_entry_path(Path::new(entry_path)),
+                Err(RejectReason::AbsolutePath),
+                "{}",
+                entry_path
+            );
+        }
+    }
+
+    #[test]
+    fn windows_prefixed_paths_stay_a_single_name() {
+        // Backslashes and drive letters aren't separators or roots here, so
+        // these end up as oddly named files inside the installation.
+        let relative_path = relative_entry_path(Path::new("CDDA/C:\\..\\..\\evil")).unwrap();
+        assert_eq!(relative_path.components().count(), 1);
+
+        let directory = TestDirectory::create();
+        let destination_path = directory.join("CDDA");
+        let report = TarFixture::new()
+            .file("CDDA/..\\..\\evil", b"x")
+            .unpack(&destination_path);
+        assert!(report.rejected_entries.is_empty());
+        assert!(destination_path.join("..\\..\\evil").is_file());
+        assert!(!directory.path().parent().unwrap().join("evil").exists());
+    }
+
+    #[test]
+    fn traversing_tar_entries_are_rejected_and_the_rest_extracted() {
+        let directory = TestDirectory::create();
+        let destination_path = directory.join("CDDA");
+        let report = TarFixture::new()
+            .file("CDDA/../evil", b"x")
+            .file("CDDA/data/../../evil2", b"x")
+            .file("CDDA/ok", b"ok")
+            .unpack(&destination_path);
+
+        assert_eq!(
+            rejection_of(&report, "CDDA/../evil"),
+            Some(&RejectReason::ParentTraversal)
+        );
+        assert_eq!(
+            rejection_of(&report, "CDDA/data/../../evil2"),
+            Some(&RejectReason::ParentTraversal)
+        );
+        assert!(!directory.join("evil").exists());
+        assert!(!directory.join("evil2").exists());
+        assert_eq!(fs::read(destination_path.join("ok")).unwrap(), b"ok");
+        assert_eq!(report.extracted_entries, 1);
+    }
+
+    #[test]
+    fn symlinks_escaping_the_installation_are_rejected() {
+        let directory = TestDirectory::create();
+        let destination_path = directory.join("CDDA");
+        let report = TarFixture::new()
+            .file("CDDA/data/a.json", b"[]")
+            .symlink("CDDA/up", "../outside")
+            .symlink("CDDA/absolute", "/etc")
+            .symlink("CDDA/data/deep", "../../outside")
+            .symlink("CDDA/sub", "data")
+            .symlink("CDDA/through", "sub/../..")
+            .symlink("CDDA/data/sibling", "../data/a.json")
+            .unpack(&destination_path);
+
+        for path in ["CDDA/up", "CDDA/absolute", "CDDA/data/deep", "CDDA/through"] {
+            assert!(
+                matches!(
+                    rejection_of(&report, path),
+                    Some(RejectReason::LinkTargetOutside(_))
+                ),
+                "{}",
+                path
+            );
+            assert!(
+                fs::symlink_metadata(destination_path.join(path.trim_start_matches("CDDA/")))
+                    .is_err()
+            );
+        }
+        assert!(destination_path.join("sub").is_symlink());
+        assert_eq!(
+            fs::read(destination_path.join("data/sibling")).unwrap(),
+            b"[]"
+        );
+    }
+
+    #[test]
+    fn entries_replace_existing_symlinks_instead_of_writing_through() {
+        let directory = TestDirectory::create();
+        let destination_path = directory.join("CDDA");
+        let outside_path = directory.join("outside");
+        fs::create_dir_all(&destination_path).unwrap();
+        fs::create_dir_all(&outside_path).unwrap();
+        fs::write(outside_path.join("target"), b"untouched").unwrap();
+        symlink(outside_path.join("target"), destination_path.join("link")).unwrap();
+        symlink(&outside_path, destination_path.join("sub")).unwrap();
+
+        let report = TarFixture::new()
+            .file("CDDA/link", b"new")
+            .file("CDDA/sub/evil", b"x")
+            .file("CDDA/sub/new/evil", b"x")
+            .unpack(&destination_path);
+
+        assert_eq!(fs::read(outside_path.join("target")).unwrap(), b"untouched");
+        assert!(!destination_path.join("link").is_symlink());
+        assert_eq!(fs::read(destination_path.join("link")).unwrap(), b"new");
+        assert_eq!(
+            rejection_of(&report, "CDDA/sub/evil"),
+            Some(&RejectReason::ThroughOutsideSymlink)
+        );
+        assert_eq!(
+            rejection_of(&report, "CDDA/sub/new/evil"),
+            Some(&RejectReason::ThroughOutsideSymlink)
+        );
+        assert!(!outside_path.join("evil").exists());
+        assert!(!outside_path.join("new").exists());
+    }
+
+    #[test]
+    fn rejected_symlinks_leave_nothing_to_write_through() {
+        let directory = TestDirectory::create();
+        let destination_path = directory.join("CDDA");
+        let outside_path = directory.join("outside");
+        fs::create_dir_all(&outside_path).unwrap();
+
+        let report = TarFixture::new()
+            .symlink("CDDA/out", outside_path.to_str().unwrap())
+            .file("CDDA/out/evil", b"x")
+            .unpack(&destination_path);
+
+        assert!(matches!(
+            rejection_of(&report, "CDDA/out"),
+            Some(RejectReason::LinkTargetOutside(_))
+        ));
+        assert!(!outside_path.join("evil").exists());
+        assert!(destination_path.join("out/evil").is_file());
+    }
+
+    #[test]
+    fn hardlinks_must_point_at_extracted_files() {
+        let directory = TestDirectory::create();
+        let destination_path = directory.join("CDDA");
+        let outside_path = directory.join("outside");
+        fs::create_dir_all(&destination_path).unwrap();
+        fs::write(&outside_path, b"secret").unwrap();
+        symlink(&outside_path, destination_path.join("to_outside")).unwrap();
+
+        let report = TarFixture::new()
+            .file("CDDA/a", b"a")
+            .hardlink("CDDA/same", "CDDA/a")
+            .hardlink("CDDA/absolute", "/etc/passwd")
+            .hardlink("CDDA/up", "CDDA/../outside")
+            .hardlink("CDDA/missing", "CDDA/nothing")
+            .hardlink("CDDA/via_symlink", "CDDA/to_outside")
+            .unpack(&destination_path);
+
+        assert_eq!(
+            fs::metadata(destination_path.join("same")).unwrap().ino(),
+            fs::metadata(destination_path.join("a")).unwrap().ino()
+        );
+        assert!(matches!(
+            rejection_of(&report, "CDDA/absolute"),
+            Some(RejectReason::LinkTargetOutside(_))
+        ));
+        assert!(matches!(
+            rejection_of(&report, "CDDA/up"),
+            Some(RejectReason::LinkTargetOutside(_))
+        ));
+        assert!(matches!(
+            rejection_of(&report, "CDDA/missing"),
+            Some(RejectReason::HardlinkTargetMissing(_))
+        ));
+        assert!(matches!(
+            rejection_of(&report, "CDDA/via_symlink"),
+            Some(RejectReason::HardlinkTargetMissing(_))
+        ));
+        assert_eq!(fs::metadata(&outside_path).unwrap().nlink(), 1);
+    }
+
+    #[test]
+    fn device_entries_are_rejected() {
+        let directory = TestDirectory::create();
+        let destination_path = directory.join("CDDA");
+        let report = TarFixture::new()
+            .entry("CDDA/char", EntryType::Char, "", b"")
+            .entry("CDDA/block", EntryType::Block, "", b"")
+            .entry("CDDA/fifo", EntryType::Fifo, "", b"")
+            .unpack(&destination_path);
+
+        for path in ["CDDA/char", "CDDA/block", "CDDA/fifo"] {
+            assert_eq!(
+                rejection_of(&report, path),
+                Some(&RejectReason::SpecialFile)
+            );
+            assert!(
+                fs::symlink_metadata(destination_path.join(path.trim_start_matches("CDDA/")))
+                    .is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn zip_entries_get_the_same_checks() {
+        let directory = TestDirectory::create();
+        let destination_path = directory.join("CDDA");
+        let archive_path = directory.join("archive.zip");
+        let options = ::zip::write::SimpleFileOptions::default()
+            .compression_method(::zip::CompressionMethod::Stored);
+        let mut writer = ::zip::ZipWriter::new(File::create(&archive_path).unwrap());
+        writer.start_file("CDDA/../evil", options).unwrap();
+        writer.write_all(b"x").unwrap();
+        writer
+            .add_symlink("CDDA/up", "../../outside", options)
+            .unwrap();
+        writer.start_file("CDDA/ok", options).unwrap();
+        writer.write_all(b"ok").unwrap();
+        writer.finish().unwrap();
+
+        let report = zip::unpack(
+            &ArchiveFilePath::new(archive_path),
+            SafeExtractor::new(&destination_path).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            rejection_of(&report, "CDDA/../evil"),
+            Some(&RejectReason::ParentTraversal)
+        );
+        assert!(matches!(
+            rejection_of(&report, "CDDA/up"),
+            Some(RejectReason::LinkTargetOutside(_))
+        ));
+        assert!(!directory.join("evil").exists());
+        assert_eq!(fs::read(destination_path.join("ok")).unwrap(), b"ok");
+    }
+}
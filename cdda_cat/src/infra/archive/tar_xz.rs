@@ -0,0 +1,6 @@
+use std::io::Read;
+use xz2::read::XzDecoder;
+
+pub fn decoder<R: Read>(compressed: R) -> XzDecoder<R> {
+    XzDecoder::new(compressed)
+}
@@ -0,0 +1,7 @@
+use anyhow::Error;
+use std::io::{BufReader, Read};
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+pub fn decoder<'a, R: Read>(compressed: R) -> Result<ZstdDecoder<'a, BufReader<R>>, Error> {
+    Ok(ZstdDecoder::new(compressed)?)
+}
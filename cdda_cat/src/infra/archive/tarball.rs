@@ -0,0 +1,141 @@
+use anyhow::{anyhow, Context, Error};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tar::EntryType;
+
+use crate::infra::interrupt;
+
+use super::safe_extract::{EntryKind, ExtractionReport, SafeExtractor};
+use super::unpacker::unreadable_entry_path;
+
+fn entry_kind<R: Read>(entry: &tar::Entry<R>) -> Result<EntryKind, Error> {
+    let link_name = || -> Result<_, Error> {
+        Ok(entry
+            .link_name()?
+            .ok_or_else(|| anyhow!("Link entry without a target"))?
+            .into_owned())
+    };
+
+    Ok(match entry.header().entry_type() {
+        EntryType::Regular | EntryType::Continuous | EntryType::GNUSparse => EntryKind::File,
+        EntryType::Directory => EntryKind::Directory,
+        EntryType::Symlink => EntryKind::Symlink(link_name()?),
+        EntryType::Link => EntryKind::Hardlink(link_name()?),
+        EntryType::Char | EntryType::Block | EntryType::Fifo => EntryKind::SpecialFile,
+        EntryType::XGlobalHeader
+        | EntryType::XHeader
+        | EntryType::GNULongName
+        | EntryType::GNULongLink => EntryKind::Metadata,
+        entry_type => EntryKind::Unsupported(format!("{:?}", entry_type)),
+    })
+}
+
+/// Extracts every entry of an already decompressed tar stream in a single
+/// pass. Progress is reported by whoever reads the compressed bytes.
+pub fn unpack<R: Read>(
+    decompressed: R,
+    mut extractor: SafeExtractor,
+) -> Result<ExtractionReport, Error> {
+    let mut archive = tar::Archive::new(decompressed);
+
+    for (i, entry) in archive.entries()?.enumerate() {
+        interrupt::check()?;
+        match entry {
+            Ok(mut entry) => match entry.path() {
+                Ok(entry_path) => {
+                    let entry_path = entry_path.into_owned();
+                    let mode = entry.header().mode().ok();
+                    if let Err(error) = entry_kind(&entry)
+                        .and_then(|kind| extractor.extract(&entry_path, kind, mode, &mut entry))
+                    {
+                        extractor.fail(&entry_path, error);
+                    }
+                }
+                Err(error) => extractor.fail(&unreadable_entry_path(i), error.into()),
+            },
+            Err(error) => extractor.fail(&unreadable_entry_path(i), error.into()),
+        }
+    }
+
+    extractor.finish()
+}
+
+/// Writes `relative_paths` below `base_path` into a tar stream, keeping
+/// symlinks as symlinks. Returns the writer once the tarball is complete.
+pub fn pack<W: Write>(writer: W, base_path: &Path, relative_paths: &[PathBuf]) -> Result<W, Error> {
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+    for relative_path in relative_paths {
+        let path = base_path.join(relative_path);
+        if path.is_dir() {
+            builder.append_dir_all(relative_path, &path)
+        } else {
+            builder.append_path_with_name(&path, relative_path)
+        }
+        .with_context(|| format!("Failed to archive {}", path.display()))?;
+    }
+
+    Ok(builder.into_inner()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::test_directory::TestDirectory;
+    use std::fs;
+    use std::io::Cursor;
+
+    fn tarball(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, path, *data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn failed_entries_are_collected_and_the_rest_extracted() {
+        let directory = TestDirectory::create();
+        let destination_path = directory.join("CDDA");
+        let bytes = tarball(&[
+            ("CDDA/blocker", b"a file"),
+            ("CDDA/blocker/child", b"needs blocker to be a directory"),
+            ("CDDA/after", b"still extracted"),
+        ]);
+
+        let report = unpack(
+            Cursor::new(bytes),
+            SafeExtractor::new(&destination_path).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(report.failed_entries.len(), 1);
+        assert_eq!(
+            report.failed_entries[0].path,
+            PathBuf::from("CDDA/blocker/child")
+        );
+        assert!(!report.failed_entries[0].error.is_empty());
+        assert_eq!(report.extracted_entries, 2);
+        assert!(destination_path.join("after").is_file());
+    }
+
+    #[test]
+    fn truncated_archives_report_failed_entries() {
+        let directory = TestDirectory::create();
+        let destination_path = directory.join("CDDA");
+        let mut bytes = tarball(&[("CDDA/a", &[7; 2048]), ("CDDA/b", &[7; 2048])]);
+        bytes.truncate(1024);
+
+        let report = unpack(
+            Cursor::new(bytes),
+            SafeExtractor::new(&destination_path).unwrap(),
+        )
+        .unwrap();
+
+        assert!(!report.failed_entries.is_empty());
+        fs::remove_dir_all(&destination_path).unwrap();
+    }
+}
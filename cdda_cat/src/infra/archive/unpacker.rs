@@ -1,15 +1,41 @@
-use anyhow::Error;
+use anyhow::{anyhow, Context, Error};
 use cdda_cat_data::entities::{ArchiveFilePath, Asset, GameEditionDirectoryPath};
 use cdda_cat_lib::installation_manager::AppSettings;
-use derive_more::{Deref, DerefMut};
-use std::path::Path;
+use derive_more::{Deref, DerefMut, Display};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use std::fs::File;
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
+
+use crate::infra::fs::content_store::ContentStore;
+use crate::infra::fs::staging::StagingDirectory;
+use crate::infra::fs::user_data::record_shipped_content;
+use crate::infra::interrupt;
+
+use super::safe_extract::{ExtractionReport, SafeExtractor};
+use super::{tar_gz, tar_xz, tar_zst, tarball, zip};
 
 #[derive(Debug, PartialEq, Deref, DerefMut)]
-pub struct ArchiveAsset(Asset);
+pub struct ArchiveAsset {
+    #[deref]
+    #[deref_mut]
+    asset: Asset,
+    content_store: Option<ContentStore>,
+}
 
 impl ArchiveAsset {
     pub fn new(asset: Asset) -> Self {
-        Self(asset)
+        Self {
+            asset,
+            content_store: None,
+        }
+    }
+
+    /// Deduplicates extracted files against other installations through
+    /// `content_store`.
+    pub fn with_content_store(mut self, content_store: Option<ContentStore>) -> Self {
+        self.content_store = content_store;
+        self
     }
 }
 
@@ -21,4 +47,360 @@ pub trait ArchiveUnpacker {
         game_edition_directory_path: &GameEditionDirectoryPath,
         settings_file_path: &Path,
     ) -> Result<(), Error>;
+
+    /// Like `unpack`, but reads the compressed archive from `compressed` as
+    /// it arrives instead of from a file on disk.
+    fn unpack_stream<R: Read>(
+        &mut self,
+        settings: &mut AppSettings,
+        compressed: R,
+        game_edition_directory_path: &GameEditionDirectoryPath,
+        settings_file_path: &Path,
+    ) -> Result<(), Error>;
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Display)]
+pub enum ArchiveFormat {
+    #[display(fmt = "tar.gz")]
+    TarGz,
+    #[display(fmt = "tar.xz")]
+    TarXz,
+    #[display(fmt = "tar.zst")]
+    TarZst,
+    #[display(fmt = "zip")]
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Longest magic number we need to look at, which is xz's.
+    pub const MAGIC_BYTES_LENGTH: usize = 6;
+
+    pub fn from_magic_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0x1f, 0x8b, ..] => Some(ArchiveFormat::TarGz),
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => Some(ArchiveFormat::TarXz),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(ArchiveFormat::TarZst),
+            [b'P', b'K', 0x03, 0x04, ..] | [b'P', b'K', 0x05, 0x06, ..] => Some(ArchiveFormat::Zip),
+            _ => None,
+        }
+    }
+
+    /// Wraps a compressed tarball stream into its decompressed one. Zip can't
+    /// be read front to back since its index sits at the end of the file.
+    pub fn tar_decoder<'a, R: Read + 'a>(self, compressed: R) -> Result<Box<dyn Read + 'a>, Error> {
+        Ok(match self {
+            ArchiveFormat::TarGz => Box::new(tar_gz::decoder(compressed)),
+            ArchiveFormat::TarXz => Box::new(tar_xz::decoder(compressed)),
+            ArchiveFormat::TarZst => Box::new(tar_zst::decoder(compressed)?),
+            ArchiveFormat::Zip => {
+                return Err(anyhow!("Zip archives can't be extracted as a stream"))
+            }
+        })
+    }
+
+    /// Peeks at the start of `compressed` and hands back a reader that still
+    /// yields those bytes.
+    pub fn detect_stream<R: Read>(mut compressed: R) -> Result<(Self, impl Read), Error> {
+        let mut magic_bytes = Vec::with_capacity(Self::MAGIC_BYTES_LENGTH);
+        (&mut compressed)
+            .take(Self::MAGIC_BYTES_LENGTH as u64)
+            .read_to_end(&mut magic_bytes)
+            .context("Failed to read archive header")?;
+        let archive_format = Self::from_magic_bytes(&magic_bytes)
+            .ok_or_else(|| anyhow!("Unsupported or unknown archive format"))?;
+
+        Ok((archive_format, Cursor::new(magic_bytes).chain(compressed)))
+    }
+
+    pub fn detect(archive_file_path: &ArchiveFilePath) -> Result<Self, Error> {
+        let mut magic_bytes = Vec::with_capacity(Self::MAGIC_BYTES_LENGTH);
+        read_archive_file(archive_file_path)?
+            .take(Self::MAGIC_BYTES_LENGTH as u64)
+            .read_to_end(&mut magic_bytes)
+            .with_context(|| {
+                format!(
+                    "Failed to read archive header of: {}",
+                    archive_file_path.display()
+                )
+            })?;
+
+        Self::from_magic_bytes(&magic_bytes).ok_or_else(|| {
+            anyhow!(
+                "Unsupported or unknown archive format: {}",
+                archive_file_path.display()
+            )
+        })
+    }
+}
+
+pub fn read_archive_file(archive_file_path: &ArchiveFilePath) -> Result<File, Error> {
+    File::open(archive_file_path.as_path()).with_context(|| {
+        format!(
+            "Failed to read archive file: {}",
+            archive_file_path.display()
+        )
+    })
+}
+
+/// Release archives wrap everything inside a single top level directory
+/// (e.g. `cataclysmdda-0.G/`), which we replace with our own
+/// installation directory name.
+pub fn strip_top_level_component(entry_path: &Path) -> PathBuf {
+    let mut components = entry_path.components();
+    components.next();
+    components.as_path().to_path_buf()
+}
+
+/// Stand-in path for entries whose header we couldn't even read.
+pub fn unreadable_entry_path(index: usize) -> PathBuf {
+    PathBuf::from(format!("<unreadable entry #{}>", index + 1))
+}
+
+/// Tracks how many compressed bytes of the archive were consumed so far,
+/// which we know up front without walking the archive first.
+pub fn extraction_progress_bar(total_bytes: u64) -> Result<ProgressBar, Error> {
+    let progress_bar = ProgressBar::new(total_bytes);
+    progress_bar.set_style(ProgressStyle::with_template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")?
+    .progress_chars("#>-"));
+    progress_bar.set_message("Extracting archive entries..");
+
+    Ok(progress_bar)
+}
+
+/// Extracts `archive_file_path` with `extractor` in a single pass.
+pub fn unpack_archive_file(
+    archive_format: ArchiveFormat,
+    archive_file_path: &ArchiveFilePath,
+    extractor: SafeExtractor,
+) -> Result<ExtractionReport, Error> {
+    if archive_format == ArchiveFormat::Zip {
+        return zip::unpack(archive_file_path, extractor);
+    }
+
+    let archive_file = read_archive_file(archive_file_path)?;
+    let progress_bar = extraction_progress_bar(archive_file.metadata()?.len())?;
+    let extraction_report = tarball::unpack(
+        archive_format.tar_decoder(progress_bar.wrap_read(archive_file))?,
+        extractor,
+    )?;
+    progress_bar.finish_with_message("Finished unpacking!");
+
+    Ok(extraction_report)
+}
+
+impl ArchiveAsset {
+    /// Extracts into a staging directory with `extract`, verifies the result
+    /// and only then swaps it into place and registers the installation.
+    fn install_with<F>(
+        &mut self,
+        settings: &mut AppSettings,
+        game_edition_directory_path: &GameEditionDirectoryPath,
+        settings_filepath: &Path,
+        extract: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce(SafeExtractor) -> Result<ExtractionReport, Error>,
+    {
+        let destination_path =
+            game_edition_directory_path.join(settings.decompressed_game_directory_path.as_path());
+        let staging_directory = StagingDirectory::new(&destination_path)?;
+        let staging_path = staging_directory.path();
+
+        let content_store = match self.content_store.take() {
+            Some(content_store) if !content_store.is_usable_for(staging_path)? => {
+                eprintln!("Content store is on another filesystem, skipping deduplication.");
+                None
+            }
+            content_store => content_store,
+        };
+        let extractor = SafeExtractor::new(staging_path)?.with_content_store(content_store);
+        let extraction_report = extract(extractor)?;
+        extraction_report.print_rejected_entries();
+        extraction_report.print_failed_entries();
+        if !extraction_report.failed_entries.is_empty() {
+            return Err(anyhow!(
+                "Failed to extract {} archive entries, so {} was left untouched!",
+                extraction_report.failed_entries.len(),
+                destination_path.display()
+            ));
+        }
+
+        let launcher_path = staging_path.join(settings.launcher_name.as_str());
+        if !launcher_path.is_file() {
+            return Err(anyhow!(
+                "Extracted archive has no {} in it, so {} was left untouched!",
+                settings.launcher_name.as_str(),
+                destination_path.display()
+            ));
+        }
+
+        record_shipped_content(staging_path)?;
+
+        interrupt::check()?;
+        staging_directory.commit()?;
+
+        if extraction_report.deduplicated_entries > 0 {
+            println!(
+                "Shared {} files with other installations, saving {}",
+                extraction_report.deduplicated_entries,
+                HumanBytes(extraction_report.deduplicated_bytes)
+            );
+        }
+
+        self.game_edition_directory_path = game_edition_directory_path.to_owned();
+
+        if !settings.installed_games.contains(self) {
+            settings.installed_games.push(self.to_owned());
+            settings.write_to_file(settings_filepath)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ArchiveUnpacker for ArchiveAsset {
+    fn unpack(
+        &mut self,
+        settings: &mut AppSettings,
+        archive_file_path: &ArchiveFilePath,
+        game_edition_directory_path: &GameEditionDirectoryPath,
+        settings_filepath: &Path,
+    ) -> Result<(), Error> {
+        let archive_format = ArchiveFormat::detect(archive_file_path)?;
+        println!("Starting to unpack {} archive..", archive_format);
+
+        self.install_with(
+            settings,
+            game_edition_directory_path,
+            settings_filepath,
+            |extractor| unpack_archive_file(archive_format, archive_file_path, extractor),
+        )
+    }
+
+    fn unpack_stream<R: Read>(
+        &mut self,
+        settings: &mut AppSettings,
+        compressed: R,
+        game_edition_directory_path: &GameEditionDirectoryPath,
+        settings_filepath: &Path,
+    ) -> Result<(), Error> {
+        let (archive_format, mut compressed) = ArchiveFormat::detect_stream(compressed)?;
+        if archive_format == ArchiveFormat::Zip {
+            return Err(anyhow!(
+                "Zip archives can't be unpacked while downloading, retry without --stream"
+            ));
+        }
+        println!("Starting to unpack {} archive stream..", archive_format);
+
+        self.install_with(
+            settings,
+            game_edition_directory_path,
+            settings_filepath,
+            |extractor| {
+                let extraction_report =
+                    tarball::unpack(archive_format.tar_decoder(&mut compressed)?, extractor)?;
+                // Tar stops reading at its end marker, but we only trust the
+                // result once the whole download arrived.
+                io::copy(&mut compressed, &mut io::sink())?;
+                Ok(extraction_report)
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::test_directory::TestDirectory;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::str::FromStr;
+
+    #[test]
+    fn detects_archive_formats_by_magic_bytes() {
+        assert_eq!(
+            ArchiveFormat::from_magic_bytes(&[0x1f, 0x8b, 0x08, 0x00]),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(
+            ArchiveFormat::from_magic_bytes(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]),
+            Some(ArchiveFormat::TarXz)
+        );
+        assert_eq!(
+            ArchiveFormat::from_magic_bytes(&[0x28, 0xb5, 0x2f, 0xfd, 0x04]),
+            Some(ArchiveFormat::TarZst)
+        );
+        assert_eq!(
+            ArchiveFormat::from_magic_bytes(b"PK\x03\x04rest"),
+            Some(ArchiveFormat::Zip)
+        );
+        // An empty zip only has its end of central directory record.
+        assert_eq!(
+            ArchiveFormat::from_magic_bytes(b"PK\x05\x06"),
+            Some(ArchiveFormat::Zip)
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_and_truncated_headers() {
+        assert_eq!(ArchiveFormat::from_magic_bytes(b""), None);
+        assert_eq!(ArchiveFormat::from_magic_bytes(&[0x1f]), None);
+        assert_eq!(ArchiveFormat::from_magic_bytes(&[0xfd, b'7', b'z']), None);
+        assert_eq!(ArchiveFormat::from_magic_bytes(b"PK\x07\x08"), None);
+        assert_eq!(ArchiveFormat::from_magic_bytes(b"<!DOCTYPE html>"), None);
+    }
+
+    #[test]
+    fn detect_stream_keeps_the_peeked_bytes() {
+        let (archive_format, mut stream) =
+            ArchiveFormat::detect_stream(&[0x1f, 0x8b, 1, 2, 3, 4, 5, 6][..]).unwrap();
+        let mut bytes = Vec::new();
+        stream.read_to_end(&mut bytes).unwrap();
+
+        assert_eq!(archive_format, ArchiveFormat::TarGz);
+        assert_eq!(bytes, vec![0x1f, 0x8b, 1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn installs_with_failed_entries_are_refused() {
+        let directory = TestDirectory::create();
+        let archive_path = directory.join("release.tar.gz");
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            File::create(&archive_path).unwrap(),
+            Compression::fast(),
+        ));
+        let mut settings = AppSettings::default();
+        for (path, data) in [
+            (settings.launcher_name.to_string(), &b"#!/bin/sh"[..]),
+            ("blocker".to_string(), b"a file"),
+            ("blocker/child".to_string(), b"can't be written"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o755);
+            builder
+                .append_data(&mut header, format!("cataclysmdda-0.G/{}", path), data)
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let game_edition_directory_path =
+            GameEditionDirectoryPath::from_str(directory.join("0.G").to_str().unwrap()).unwrap();
+        let error = ArchiveAsset::new(Asset::default())
+            .unpack(
+                &mut settings,
+                &ArchiveFilePath::new(archive_path),
+                &game_edition_directory_path,
+                &directory.join("settings.json"),
+            )
+            .unwrap_err();
+
+        assert!(error
+            .to_string()
+            .starts_with("Failed to extract 1 archive entries"));
+        assert!(!game_edition_directory_path
+            .join(settings.decompressed_game_directory_path.as_path())
+            .exists());
+        assert!(settings.installed_games.is_empty());
+    }
 }
@@ -0,0 +1,143 @@
+use anyhow::{anyhow, Error};
+use cdda_cat_data::entities::ArchiveFilePath;
+use std::io::{Read, Seek};
+use std::path::PathBuf;
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::infra::interrupt;
+
+use super::safe_extract::{EntryKind, ExtractionReport, SafeExtractor};
+use super::unpacker::{extraction_progress_bar, read_archive_file, unreadable_entry_path};
+
+const S_IFMT: u32 = 0o170000;
+const S_IFREG: u32 = 0o100000;
+
+fn entry_kind(entry: &mut ::zip::read::ZipFile) -> Result<EntryKind, Error> {
+    if entry.is_dir() {
+        return Ok(EntryKind::Directory);
+    }
+    if entry.is_symlink() {
+        let mut target = String::new();
+        entry.read_to_string(&mut target)?;
+        return Ok(EntryKind::Symlink(PathBuf::from(target)));
+    }
+
+    Ok(match entry.unix_mode().map(|mode| mode & S_IFMT) {
+        None | Some(0) | Some(S_IFREG) => EntryKind::File,
+        Some(_) => EntryKind::SpecialFile,
+    })
+}
+
+/// Only these are built in, anything else would fail halfway through.
+fn is_supported_compression(compression: CompressionMethod) -> bool {
+    matches!(
+        compression,
+        CompressionMethod::Stored | CompressionMethod::Deflated | CompressionMethod::Zstd
+    )
+}
+
+/// Refuses archives with entries we can't decompress before anything gets
+/// extracted.
+fn check_compression<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<(), Error> {
+    let mut unsupported_entries = Vec::new();
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+        if !is_supported_compression(entry.compression()) {
+            unsupported_entries.push(format!("{} ({})", entry.name(), entry.compression()));
+        }
+    }
+    if unsupported_entries.is_empty() {
+        return Ok(());
+    }
+
+    Err(anyhow!(
+        "Unsupported zip compression in {} entries: {}",
+        unsupported_entries.len(),
+        unsupported_entries.join(", ")
+    ))
+}
+
+pub fn unpack(
+    archive_file_path: &ArchiveFilePath,
+    mut extractor: SafeExtractor,
+) -> Result<ExtractionReport, Error> {
+    let archive_file = read_archive_file(archive_file_path)?;
+    let total_size = archive_file.metadata()?.len();
+    let mut archive = ZipArchive::new(archive_file)?;
+    check_compression(&mut archive)?;
+
+    let progress_bar = extraction_progress_bar(total_size)?;
+    for i in 0..archive.len() {
+        interrupt::check()?;
+        match archive.by_index(i) {
+            Ok(mut entry) => {
+                progress_bar.inc(entry.compressed_size());
+                let entry_path = PathBuf::from(entry.name());
+                let mode = entry.unix_mode();
+                if let Err(error) = entry_kind(&mut entry)
+                    .and_then(|kind| extractor.extract(&entry_path, kind, mode, &mut entry))
+                {
+                    extractor.fail(&entry_path, error);
+                }
+            }
+            Err(error) => extractor.fail(&unreadable_entry_path(i), error.into()),
+        }
+    }
+
+    progress_bar.finish_with_message("Finished unpacking!");
+
+    extractor.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    /// Zip of one stored entry whose method field claims `method` instead.
+    fn zip_with_method(method: u16) -> Cursor<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file(
+                "CDDA/data/a.json",
+                SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+            )
+            .unwrap();
+        writer.write_all(b"[]").unwrap();
+        let mut bytes = writer.finish().unwrap().into_inner();
+
+        // The method sits 8 bytes into the local header and 10 bytes into
+        // the central directory header.
+        for (signature, offset) in [(b"PK\x03\x04", 8), (b"PK\x01\x02", 10)] {
+            let start = bytes
+                .windows(4)
+                .position(|window| window == signature)
+                .unwrap();
+            bytes[start + offset..start + offset + 2].copy_from_slice(&method.to_le_bytes());
+        }
+
+        Cursor::new(bytes)
+    }
+
+    #[test]
+    fn accepts_stored_entries() {
+        let mut archive = ZipArchive::new(zip_with_method(0)).unwrap();
+        assert!(check_compression(&mut archive).is_ok());
+    }
+
+    #[test]
+    fn refuses_bzip2_entries_up_front() {
+        let mut archive = ZipArchive::new(zip_with_method(12)).unwrap();
+        let error = check_compression(&mut archive).unwrap_err().to_string();
+        assert!(error.starts_with("Unsupported zip compression in 1 entries"));
+        assert!(error.contains("CDDA/data/a.json"));
+    }
+
+    #[test]
+    fn refuses_deflate64_entries_up_front() {
+        let mut archive = ZipArchive::new(zip_with_method(9)).unwrap();
+        assert!(check_compression(&mut archive).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::{anyhow, Error};
+use app_dirs2::{get_app_root, AppDataType, AppInfo};
+use cdda_cat_data::entities::{InstallationId, RootDownloadDirectoryPath};
+use cdda_cat_lib::installation_manager::AppSettings;
+use std::path::{Component, Path, PathBuf};
+
+use super::content_store::ContentStore;
+
+const APP_INFO: AppInfo = AppInfo {
+    name: "cdda_cat",
+    author: "cloudyluna",
+};
+
+/// Where cdda_cat keeps its own configuration, downloads and data. Nothing
+/// is created until something gets written there.
+#[derive(Debug, Clone)]
+pub struct AppDirectories {
+    pub config_path: PathBuf,
+    pub cache_path: PathBuf,
+    pub data_path: PathBuf,
+    pub settings_file_path: PathBuf,
+}
+
+/// `profile` itself, once it is known to be a plain directory name.
+pub fn user_directory_profile(profile: &str) -> Result<&str, Error> {
+    let mut components = Path::new(profile).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(profile),
+        _ => Err(anyhow!("Invalid user directory profile name: {}", profile)),
+    }
+}
+
+impl AppDirectories {
+    pub fn new() -> Result<Self, Error> {
+        let config_path = get_app_root(AppDataType::UserConfig, &APP_INFO)?;
+        let settings_file_path = config_path.join("settings.json");
+
+        Ok(Self {
+            config_path,
+            cache_path: get_app_root(AppDataType::UserCache, &APP_INFO)?,
+            data_path: get_app_root(AppDataType::UserData, &APP_INFO)?,
+            settings_file_path,
+        })
+    }
+
+    pub fn root_download_directory_path(
+        &self,
+        settings: &AppSettings,
+    ) -> Result<RootDownloadDirectoryPath, Error> {
+        let system_download_dir = self
+            .cache_path
+            .join(settings.root_download_directory_path.as_path())
+            .into_os_string()
+            .into_string()
+            .map_err(|_| anyhow!("Cannot convert top download directory path to String"))?;
+
+        Ok(RootDownloadDirectoryPath::new(&system_download_dir))
+    }
+
+    /// User data saved from installations before they were removed.
+    pub fn preserved_user_data_path(&self) -> PathBuf {
+        self.data_path.join("preserved")
+    }
+
+    /// Save backups made with `backup create`.
+    pub fn backups_path(&self) -> PathBuf {
+        self.data_path.join("backups")
+    }
+
+    /// Names of the options each installation wrote the last time it ran,
+    /// since a user directory shared between builds doesn't tell.
+    pub fn option_names_file_path(&self, installation: &InstallationId) -> PathBuf {
+        self.data_path.join("option_names").join(format!(
+            "{}-{}.json",
+            installation.tag, installation.edition
+        ))
+    }
+
+    pub fn user_directories_path(&self) -> PathBuf {
+        self.data_path.join("userdirs")
+    }
+
+    /// The directory of user directory `profile`, which has to be a plain
+    /// directory name.
+    pub fn user_directory_path(&self, profile: &str) -> Result<PathBuf, Error> {
+        Ok(self
+            .user_directories_path()
+            .join(user_directory_profile(profile)?))
+    }
+
+    pub fn content_store(&self) -> ContentStore {
+        ContentStore::new(self.data_path.join("store"))
+    }
+}
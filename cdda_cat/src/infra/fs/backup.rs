@@ -0,0 +1,619 @@
+use anyhow::{anyhow, Context, Error};
+use chrono::{Local, NaiveDateTime};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::infra::archive::safe_extract::SafeExtractor;
+use crate::infra::archive::{tar_gz, tarball};
+
+use super::staging::sibling_path;
+use super::user_data::{archive_user_data, migrate_entry, remove_any, ConflictResolution};
+
+pub const SAVE_DIRECTORY_NAME: &str = "save";
+const WORLDS_DIRECTORY_NAME: &str = "worlds";
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+const ARCHIVE_EXTENSION: &str = ".tar.gz";
+const RESTORING_SUFFIX: &str = "restoring";
+
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub path: PathBuf,
+    /// Where it was taken from, relative to the backups directory.
+    pub location_path: PathBuf,
+    /// `None` for backups of a whole `save/` directory.
+    pub world: Option<String>,
+    pub created: NaiveDateTime,
+    /// Tells apart backups made within the same second.
+    pub sequence: u32,
+    pub size: u64,
+}
+
+/// What a backup holds below `save/`, read from the archive itself.
+#[derive(Debug, Default)]
+pub struct ArchivedSave {
+    /// Top level entries, usually worlds, with the newest modification time
+    /// of the files in them.
+    pub worlds: BTreeMap<PathBuf, u64>,
+    /// Modification time of every file, so restored worlds keep them.
+    pub modification_times: Vec<(PathBuf, u64)>,
+}
+
+/// `save/<world>`, refusing anything that isn't a plain directory name.
+pub fn world_path(world: &str) -> Result<PathBuf, Error> {
+    let mut components = Path::new(world).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(Path::new(SAVE_DIRECTORY_NAME).join(world)),
+        _ => Err(anyhow!("Invalid world name: {}", world)),
+    }
+}
+
+/// Newest modification time, in seconds, of the files below `path`.
+pub fn newest_modification_time(path: &Path) -> Result<u64, Error> {
+    let metadata =
+        fs::symlink_metadata(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if metadata.is_file() {
+        return Ok(metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default());
+    }
+
+    let mut newest = 0;
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)
+            .with_context(|| format!("Failed to read directory {}", path.display()))?
+        {
+            newest = newest.max(newest_modification_time(&entry?.path())?);
+        }
+    }
+
+    Ok(newest)
+}
+
+/// Directories in `save_path`, one per world. Hidden ones are leftovers of
+/// ours like `.World.previous`.
+pub fn list_worlds(save_path: &Path) -> Result<Vec<String>, Error> {
+    if !save_path.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut worlds = Vec::new();
+    for entry in fs::read_dir(save_path)
+        .with_context(|| format!("Failed to read directory {}", save_path.display()))?
+    {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_dir() && !name.starts_with('.') {
+            worlds.push(name);
+        }
+    }
+    worlds.sort();
+
+    Ok(worlds)
+}
+
+/// Reads `archive_path` through to the end, making sure it decompresses
+/// cleanly and holds nothing but `save/`.
+pub fn inspect(archive_path: &Path) -> Result<ArchivedSave, Error> {
+    let archive_file = File::open(archive_path)
+        .with_context(|| format!("Failed to read backup {}", archive_path.display()))?;
+    let mut decoder = tar_gz::decoder(archive_file);
+    let mut archived_save = ArchivedSave::default();
+
+    let mut read_entries = || -> Result<(), Error> {
+        for entry in tar::Archive::new(&mut decoder).entries()? {
+            let mut entry = entry?;
+            let entry_path = entry.path()?.into_owned();
+            let mut components = entry_path.components();
+            if components.next() != Some(Component::Normal(SAVE_DIRECTORY_NAME.as_ref()))
+                || !components
+                    .clone()
+                    .all(|c| matches!(c, Component::Normal(_)))
+            {
+                return Err(anyhow!(
+                    "{} is outside of {}/",
+                    entry_path.display(),
+                    SAVE_DIRECTORY_NAME
+                ));
+            }
+            let relative_path = components.as_path().to_path_buf();
+            let world = match components.next() {
+                Some(world) => PathBuf::from(world.as_os_str()),
+                None => continue,
+            };
+
+            let modification_time = entry.header().mtime()?;
+            let newest = archived_save.worlds.entry(world).or_default();
+            if entry.header().entry_type().is_file() {
+                *newest = (*newest).max(modification_time);
+                archived_save
+                    .modification_times
+                    .push((relative_path, modification_time));
+            }
+            io::copy(&mut entry, &mut io::sink())?;
+        }
+        // The gzip checksum only gets checked once the stream is exhausted.
+        io::copy(&mut decoder, &mut io::sink())?;
+        Ok(())
+    };
+    read_entries().with_context(|| format!("Backup {} is damaged", archive_path.display()))?;
+
+    if archived_save.worlds.is_empty() {
+        return Err(anyhow!(
+            "Backup {} has no worlds in it",
+            archive_path.display()
+        ));
+    }
+
+    Ok(archived_save)
+}
+
+/// Archives of `save/` directories, kept per location and per world.
+#[derive(Debug, Clone)]
+pub struct BackupStore {
+    root_path: PathBuf,
+}
+
+impl BackupStore {
+    pub fn new(root_path: PathBuf) -> Self {
+        Self { root_path }
+    }
+
+    fn directory_path(&self, location_path: &Path, world: Option<&str>) -> PathBuf {
+        let location_path = self.root_path.join(location_path);
+        match world {
+            Some(world) => location_path.join(WORLDS_DIRECTORY_NAME).join(world),
+            None => location_path.join(SAVE_DIRECTORY_NAME),
+        }
+    }
+
+    /// Archives `world`, or all of `save/` when `None`, of the game directory
+    /// at `base_path`.
+    pub fn create(
+        &self,
+        location_path: &Path,
+        base_path: &Path,
+        world: Option<&str>,
+    ) -> Result<PathBuf, Error> {
+        let relative_path = match world {
+            Some(world) => world_path(world)?,
+            None => PathBuf::from(SAVE_DIRECTORY_NAME),
+        };
+        if !base_path.join(&relative_path).is_dir() {
+            return Err(anyhow!(
+                "{} has no {}",
+                base_path.display(),
+                relative_path.display()
+            ));
+        }
+
+        let directory_path = self.directory_path(location_path, world);
+        let timestamp = Local::now().format(TIMESTAMP_FORMAT).to_string();
+        // Rotation can free up earlier names of the same second, so only
+        // ever count upwards from the newest one.
+        let sequence = self
+            .list(Some(location_path))?
+            .iter()
+            .filter(|backup| {
+                backup.world.as_deref() == world
+                    && backup.created.format(TIMESTAMP_FORMAT).to_string() == timestamp
+            })
+            .map(|backup| backup.sequence + 1)
+            .max()
+            .unwrap_or(1);
+        let archive_path = directory_path.join(match sequence {
+            1 => format!("{}{}", timestamp, ARCHIVE_EXTENSION),
+            _ => format!("{}-{}{}", timestamp, sequence, ARCHIVE_EXTENSION),
+        });
+        archive_user_data(base_path, &[relative_path], &archive_path)?;
+
+        Ok(archive_path)
+    }
+
+    /// Backups of `location_path`, or of every location, oldest first.
+    pub fn list(&self, location_path: Option<&Path>) -> Result<Vec<Backup>, Error> {
+        let mut archive_paths = Vec::new();
+        find_archives(
+            &self.root_path.join(location_path.unwrap_or(Path::new(""))),
+            &mut archive_paths,
+        )?;
+
+        let mut backups = Vec::new();
+        for archive_path in archive_paths {
+            if let Some(backup) = self.describe(archive_path)? {
+                backups.push(backup);
+            }
+        }
+        backups.sort_by(|a, b| {
+            (&a.location_path, &a.world, a.created, a.sequence).cmp(&(
+                &b.location_path,
+                &b.world,
+                b.created,
+                b.sequence,
+            ))
+        });
+
+        Ok(backups)
+    }
+
+    /// Makes sense of where `archive_path` sits in the store, skipping files
+    /// that don't belong there.
+    fn describe(&self, archive_path: PathBuf) -> Result<Option<Backup>, Error> {
+        let relative_path = match archive_path.strip_prefix(&self.root_path) {
+            Ok(relative_path) => relative_path,
+            Err(_) => return Ok(None),
+        };
+        let components: Vec<&str> = relative_path
+            .iter()
+            .filter_map(|component| component.to_str())
+            .collect();
+        let (location_path, world, archive_name) = match components.as_slice() {
+            [kind, name, SAVE_DIRECTORY_NAME, archive_name] => {
+                (Path::new(kind).join(name), None, archive_name)
+            }
+            [kind, name, WORLDS_DIRECTORY_NAME, world, archive_name] => (
+                Path::new(kind).join(name),
+                Some(world.to_string()),
+                archive_name,
+            ),
+            _ => return Ok(None),
+        };
+        // `<timestamp>.tar.gz`, or `<timestamp>-<sequence>.tar.gz` after the
+        // first one of a second.
+        let stem = match archive_name.strip_suffix(ARCHIVE_EXTENSION) {
+            Some(stem) => stem,
+            None => return Ok(None),
+        };
+        let sequence = match stem.get(15..) {
+            Some("") => Some(1),
+            Some(suffix) => suffix.strip_prefix('-').and_then(|n| n.parse().ok()),
+            None => None,
+        };
+        let created = stem
+            .get(..15)
+            .and_then(|timestamp| NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok());
+        let (created, sequence) = match (created, sequence) {
+            (Some(created), Some(sequence)) => (created, sequence),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(Backup {
+            size: fs::metadata(&archive_path)?.len(),
+            path: archive_path,
+            location_path,
+            world,
+            created,
+            sequence,
+        }))
+    }
+
+    /// The latest backup of `world`, or of all of `save/` when `None`.
+    pub fn newest(
+        &self,
+        location_path: &Path,
+        world: Option<&str>,
+    ) -> Result<Option<Backup>, Error> {
+        Ok(self
+            .list(Some(location_path))?
+            .into_iter()
+            .rev()
+            .find(|backup| backup.world.as_deref() == world))
+    }
+
+    /// Removes all but the `keep` newest backups of each world and `save/`
+    /// directory, and returns the removed ones. Zero keeps everything.
+    pub fn prune(&self, location_path: Option<&Path>, keep: usize) -> Result<Vec<Backup>, Error> {
+        if keep == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut groups: BTreeMap<(PathBuf, Option<String>), Vec<Backup>> = BTreeMap::new();
+        for backup in self.list(location_path)? {
+            groups
+                .entry((backup.location_path.clone(), backup.world.clone()))
+                .or_default()
+                .push(backup);
+        }
+
+        let mut removed = Vec::new();
+        for (_, backups) in groups {
+            let excess = backups.len().saturating_sub(keep);
+            for backup in backups.into_iter().take(excess) {
+                fs::remove_file(&backup.path)
+                    .with_context(|| format!("Failed to remove {}", backup.path.display()))?;
+                removed.push(backup);
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+fn find_archives(directory_path: &Path, archive_paths: &mut Vec<PathBuf>) -> Result<(), Error> {
+    if !directory_path.is_dir() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(directory_path)
+        .with_context(|| format!("Failed to read directory {}", directory_path.display()))?
+    {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_archives(&path, archive_paths)?;
+        } else if path.to_string_lossy().ends_with(ARCHIVE_EXTENSION) {
+            archive_paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Worlds of `archived_save` that were changed in `save_path` after the
+/// backup was made.
+pub fn find_newer_worlds(
+    archived_save: &ArchivedSave,
+    save_path: &Path,
+) -> Result<Vec<PathBuf>, Error> {
+    let mut newer_worlds = Vec::new();
+    for (world, archived_time) in archived_save.worlds.iter() {
+        let world_path = save_path.join(world);
+        if fs::symlink_metadata(&world_path).is_ok()
+            && newest_modification_time(&world_path)? > *archived_time
+        {
+            newer_worlds.push(world.to_path_buf());
+        }
+    }
+
+    Ok(newer_worlds)
+}
+
+/// Puts the worlds of the backup at `archive_path` into `save_path`,
+/// replacing the ones already there.
+pub fn restore(
+    archive_path: &Path,
+    archived_save: &ArchivedSave,
+    save_path: &Path,
+) -> Result<(), Error> {
+    let staging_path = sibling_path(save_path, RESTORING_SUFFIX);
+    if fs::symlink_metadata(&staging_path).is_ok() {
+        remove_any(&staging_path)?;
+    }
+
+    let restored = extract(archive_path, archived_save, &staging_path).and_then(|()| {
+        for world in archived_save.worlds.keys() {
+            migrate_entry(
+                &staging_path,
+                save_path,
+                world,
+                ConflictResolution::Overwrite,
+                true,
+            )?;
+        }
+        Ok(())
+    });
+    if fs::symlink_metadata(&staging_path).is_ok() {
+        remove_any(&staging_path)?;
+    }
+
+    restored
+}
+
+/// Unpacks the backup into `staging_path`, dropping its `save/` prefix.
+fn extract(
+    archive_path: &Path,
+    archived_save: &ArchivedSave,
+    staging_path: &Path,
+) -> Result<(), Error> {
+    let archive_file = File::open(archive_path)
+        .with_context(|| format!("Failed to read backup {}", archive_path.display()))?;
+    let extraction_report = tarball::unpack(
+        tar_gz::decoder(archive_file),
+        SafeExtractor::new(staging_path)?,
+    )?;
+    extraction_report.print_rejected_entries();
+    extraction_report.print_failed_entries();
+    if !extraction_report.rejected_entries.is_empty()
+        || !extraction_report.failed_entries.is_empty()
+    {
+        return Err(anyhow!(
+            "Failed to extract backup {}, nothing was restored",
+            archive_path.display()
+        ));
+    }
+
+    for (relative_path, modification_time) in archived_save.modification_times.iter() {
+        let path = staging_path.join(relative_path);
+        File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| {
+                file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(*modification_time))
+            })
+            .with_context(|| {
+                format!("Failed to restore modification time of {}", path.display())
+            })?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::test_directory::TestDirectory;
+
+    const LOCATION: &str = "installations/0.H-Curses";
+
+    fn write_archive(directory: &TestDirectory, relative_path: &str) {
+        let archive_path = directory.join("backups").join(relative_path);
+        fs::create_dir_all(archive_path.parent().unwrap()).unwrap();
+        fs::write(archive_path, "archive").unwrap();
+    }
+
+    fn archive_names(backups: &[Backup]) -> Vec<String> {
+        backups
+            .iter()
+            .map(|backup| {
+                backup
+                    .path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn describes_archives_by_where_they_sit() {
+        let directory = TestDirectory::create();
+        for relative_path in [
+            "installations/0.H-Curses/save/20261018-120000.tar.gz",
+            "installations/0.H-Curses/worlds/World1/20261018-120000-2.tar.gz",
+            "userdirs/default/worlds/World1/20261018-120001.tar.gz",
+            // Not ours, or named by something else.
+            "installations/0.H-Curses/save/notes.tar.gz",
+            "installations/0.H-Curses/save/20261018-120000-x.tar.gz",
+            "installations/0.H-Curses/save/20261018-120000.zip",
+            "installations/0.H-Curses/other/20261018-120000.tar.gz",
+            "20261018-120000.tar.gz",
+        ] {
+            write_archive(&directory, relative_path);
+        }
+        let backup_store = BackupStore::new(directory.join("backups"));
+
+        let backups = backup_store.list(None).unwrap();
+
+        assert_eq!(
+            backups
+                .iter()
+                .map(|backup| (
+                    backup.location_path.to_string_lossy().to_string(),
+                    backup.world.clone(),
+                    backup.created.format(TIMESTAMP_FORMAT).to_string(),
+                    backup.sequence
+                ))
+                .collect::<Vec<_>>(),
+            [
+                (LOCATION.to_string(), None, "20261018-120000".to_string(), 1),
+                (
+                    LOCATION.to_string(),
+                    Some("World1".to_string()),
+                    "20261018-120000".to_string(),
+                    2
+                ),
+                (
+                    "userdirs/default".to_string(),
+                    Some("World1".to_string()),
+                    "20261018-120001".to_string(),
+                    1
+                ),
+            ]
+        );
+        assert_eq!(
+            backup_store.list(Some(Path::new(LOCATION))).unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn prunes_the_oldest_backups_of_each_world() {
+        let directory = TestDirectory::create();
+        for archive_name in [
+            "worlds/World1/20261018-120000.tar.gz",
+            "worlds/World1/20261018-120000-2.tar.gz",
+            "worlds/World1/20261018-130000.tar.gz",
+            "worlds/World2/20261018-110000.tar.gz",
+            "save/20261018-100000.tar.gz",
+            "save/20261018-140000.tar.gz",
+        ] {
+            write_archive(&directory, &format!("{}/{}", LOCATION, archive_name));
+        }
+        let backup_store = BackupStore::new(directory.join("backups"));
+
+        assert!(backup_store.prune(None, 0).unwrap().is_empty());
+        let removed = backup_store.prune(None, 2).unwrap();
+
+        assert_eq!(archive_names(&removed), ["20261018-120000.tar.gz"]);
+        assert_eq!(
+            archive_names(&backup_store.list(None).unwrap()),
+            [
+                "20261018-100000.tar.gz",
+                "20261018-140000.tar.gz",
+                "20261018-120000-2.tar.gz",
+                "20261018-130000.tar.gz",
+                "20261018-110000.tar.gz",
+            ]
+        );
+    }
+
+    #[test]
+    fn refuses_damaged_and_foreign_archives() {
+        let directory = TestDirectory::create();
+        let game_path = directory.join("CDDA");
+        fs::create_dir_all(game_path.join("save/World1")).unwrap();
+        fs::create_dir_all(game_path.join("config")).unwrap();
+        fs::write(game_path.join("save/World1/master.gsav"), "saved").unwrap();
+        fs::write(game_path.join("config/options.json"), "[]").unwrap();
+        let backup_store = BackupStore::new(directory.join("backups"));
+        let archive_path = backup_store
+            .create(Path::new(LOCATION), &game_path, None)
+            .unwrap();
+
+        let archived_save = inspect(&archive_path).unwrap();
+        assert_eq!(
+            archived_save.worlds.keys().collect::<Vec<_>>(),
+            [Path::new("World1")]
+        );
+
+        let contents = fs::read(&archive_path).unwrap();
+        let damaged_path = directory.join("damaged.tar.gz");
+        fs::write(&damaged_path, &contents[..contents.len() / 2]).unwrap();
+        assert!(inspect(&damaged_path).is_err());
+
+        let foreign_path = directory.join("foreign.tar.gz");
+        archive_user_data(&game_path, &[PathBuf::from("config")], &foreign_path).unwrap();
+        let error = inspect(&foreign_path).unwrap_err();
+        assert!(format!("{:#}", error).contains("config/ is outside of save/"));
+    }
+
+    #[test]
+    fn restores_worlds_as_they_were_backed_up() {
+        let directory = TestDirectory::create();
+        let game_path = directory.join("CDDA");
+        let save_path = game_path.join("save");
+        for world in ["World1", "World2"] {
+            fs::create_dir_all(save_path.join(world)).unwrap();
+            fs::write(save_path.join(world).join("master.gsav"), world).unwrap();
+        }
+        let backup_store = BackupStore::new(directory.join("backups"));
+        let archive_path = backup_store
+            .create(Path::new(LOCATION), &game_path, Some("World1"))
+            .unwrap();
+        let archived_save = inspect(&archive_path).unwrap();
+        let modified = newest_modification_time(&save_path.join("World1")).unwrap();
+
+        fs::write(save_path.join("World1/master.gsav"), "changed").unwrap();
+        fs::write(save_path.join("World1/new.map"), "new").unwrap();
+        restore(&archive_path, &archived_save, &save_path).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(save_path.join("World1/master.gsav")).unwrap(),
+            "World1"
+        );
+        assert!(!save_path.join("World1/new.map").exists());
+        assert_eq!(
+            newest_modification_time(&save_path.join("World1")).unwrap(),
+            modified
+        );
+        assert_eq!(
+            fs::read_to_string(save_path.join("World2/master.gsav")).unwrap(),
+            "World2"
+        );
+        assert_eq!(list_worlds(&game_path).unwrap(), ["save"]);
+    }
+}
@@ -0,0 +1,204 @@
+use anyhow::{Context, Error};
+use cdda_cat_data::entities::{ArchiveCachePolicy, RootDownloadDirectoryPath};
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// How deep edition directories sit below the root download directory:
+/// `<platform>/<tag>/<edition>`.
+pub const EDITION_DIRECTORY_DEPTH: usize = 3;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CachedArchive {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// Every `<platform>/<tag>/<edition>` directory below `root_download_directory_path`.
+pub fn list_edition_directories(
+    root_download_directory_path: &RootDownloadDirectoryPath,
+) -> Result<Vec<PathBuf>, Error> {
+    let mut directories = vec![root_download_directory_path.to_path_buf()];
+    for _ in 0..EDITION_DIRECTORY_DEPTH {
+        let mut children = Vec::new();
+        for directory in directories {
+            children.extend(
+                list_directory(&directory)?
+                    .into_iter()
+                    .filter(|path| path.is_dir() && !path.is_symlink() && !is_hidden(path)),
+            );
+        }
+        directories = children;
+    }
+
+    Ok(directories)
+}
+
+/// Downloaded archives are the regular files sitting right inside an
+/// edition directory, next to the installation itself.
+pub fn list_cached_archives(
+    root_download_directory_path: &RootDownloadDirectoryPath,
+) -> Result<Vec<CachedArchive>, Error> {
+    let mut archives = Vec::new();
+    for edition_directory in list_edition_directories(root_download_directory_path)? {
+        for path in list_directory(&edition_directory)? {
+            let metadata = fs::symlink_metadata(&path)?;
+            if metadata.is_file() && !is_part_file(&path) {
+                archives.push(CachedArchive {
+                    path,
+                    size: metadata.len(),
+                    modified: metadata.modified()?,
+                });
+            }
+        }
+    }
+    archives.sort_by_key(|archive| Reverse(archive.modified));
+
+    Ok(archives)
+}
+
+/// Picks which archives go away so the rest satisfies `policy`. Newest
+/// archives are kept first; `archives` must be sorted newest first.
+pub fn select_archives_to_prune(
+    archives: &[CachedArchive],
+    policy: &ArchiveCachePolicy,
+) -> Vec<CachedArchive> {
+    let mut total_size = 0;
+    archives
+        .iter()
+        .enumerate()
+        .filter(|(i, archive)| {
+            total_size += archive.size;
+            let over_count = policy.keep_last.is_some_and(|keep_last| *i >= keep_last);
+            let over_size = policy
+                .max_total_size
+                .is_some_and(|max_total_size| total_size > *max_total_size);
+            if over_count || over_size {
+                total_size -= archive.size;
+                true
+            } else {
+                false
+            }
+        })
+        .map(|(_, archive)| archive.clone())
+        .collect()
+}
+
+pub fn remove_archives(archives: &[CachedArchive]) -> Result<u64, Error> {
+    let mut freed = 0;
+    for archive in archives {
+        fs::remove_file(&archive.path)
+            .with_context(|| format!("Failed to remove archive: {}", archive.path.display()))?;
+        println!("Removed {}", archive.path.display());
+        freed += archive.size;
+    }
+
+    Ok(freed)
+}
+
+pub fn list_directory(directory: &Path) -> Result<Vec<PathBuf>, Error> {
+    if !directory.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut paths = fs::read_dir(directory)
+        .with_context(|| format!("Failed to read directory: {}", directory.display()))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>, Error>>()?;
+    paths.sort();
+
+    Ok(paths)
+}
+
+pub fn is_part_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "part")
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdda_cat_data::entities::ByteSize;
+    use std::time::Duration;
+
+    /// Archives of the given sizes, newest first like `list_cached_archives`
+    /// returns them.
+    fn archives(sizes: &[u64]) -> Vec<CachedArchive> {
+        sizes
+            .iter()
+            .enumerate()
+            .map(|(i, size)| CachedArchive {
+                path: PathBuf::from(format!("archive-{}", i)),
+                size: *size,
+                modified: SystemTime::UNIX_EPOCH + Duration::from_secs(1000 - i as u64),
+            })
+            .collect()
+    }
+
+    fn pruned_paths(archives: &[CachedArchive], policy: &ArchiveCachePolicy) -> Vec<String> {
+        select_archives_to_prune(archives, policy)
+            .iter()
+            .map(|archive| archive.path.display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn keeps_everything_without_limits() {
+        assert!(pruned_paths(&archives(&[10, 20, 30]), &ArchiveCachePolicy::default()).is_empty());
+    }
+
+    #[test]
+    fn keeps_the_newest_archives_up_to_a_count() {
+        let policy = ArchiveCachePolicy {
+            keep_last: Some(2),
+            ..ArchiveCachePolicy::default()
+        };
+        assert_eq!(
+            pruned_paths(&archives(&[10, 20, 30, 40]), &policy),
+            ["archive-2", "archive-3"]
+        );
+
+        let policy = ArchiveCachePolicy {
+            keep_last: Some(0),
+            ..ArchiveCachePolicy::default()
+        };
+        assert_eq!(pruned_paths(&archives(&[10, 20]), &policy).len(), 2);
+    }
+
+    #[test]
+    fn removes_the_oldest_archives_beyond_the_size_limit() {
+        let policy = ArchiveCachePolicy {
+            max_total_size: Some(ByteSize::new(50)),
+            ..ArchiveCachePolicy::default()
+        };
+        assert_eq!(
+            pruned_paths(&archives(&[20, 20, 20, 5]), &policy),
+            ["archive-2"]
+        );
+        // An archive too big on its own goes, smaller older ones still fit.
+        assert_eq!(
+            pruned_paths(&archives(&[10, 100, 30]), &policy),
+            ["archive-1"]
+        );
+    }
+
+    #[test]
+    fn applies_both_limits_together() {
+        let policy = ArchiveCachePolicy {
+            keep_last: Some(3),
+            max_total_size: Some(ByteSize::new(25)),
+            ..ArchiveCachePolicy::default()
+        };
+        assert_eq!(
+            pruned_paths(&archives(&[10, 10, 10, 1]), &policy),
+            ["archive-2", "archive-3"]
+        );
+    }
+}
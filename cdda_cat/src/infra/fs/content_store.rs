@@ -0,0 +1,222 @@
+use anyhow::{Context, Error};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use super::cache::list_directory;
+
+/// Files shared by several installations, named after their contents.
+///
+/// Installations hardlink into the store instead of keeping their own copy,
+/// so removing an installation only drops its links. An object nothing links
+/// to anymore has a link count of 1 and is garbage. Objects are read-only,
+/// writing through one link would change the file for every installation.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ContentStore {
+    root_path: PathBuf,
+}
+
+/// Hashes everything written through it.
+pub struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    pub fn into_digest(self) -> (W, String) {
+        (self.inner, format!("{:x}", self.hasher.finalize()))
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Clears the write bits of a file and every link to it.
+fn make_read_only(path: &Path) -> Result<(), Error> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    if permissions.mode() & 0o222 != 0 {
+        permissions.set_mode(permissions.mode() & !0o222);
+        fs::set_permissions(path, permissions)
+            .with_context(|| format!("Failed to make {} read-only", path.display()))?;
+    }
+
+    Ok(())
+}
+
+impl ContentStore {
+    pub fn new(root_path: PathBuf) -> Self {
+        Self { root_path }
+    }
+
+    /// Hardlinks can't cross filesystems, so the store is only usable for
+    /// installations living on the same one.
+    pub fn is_usable_for(&self, installation_path: &Path) -> Result<bool, Error> {
+        fs::create_dir_all(&self.root_path).with_context(|| {
+            format!(
+                "Failed to create content store at {}",
+                self.root_path.display()
+            )
+        })?;
+
+        Ok(fs::metadata(&self.root_path)?.dev() == fs::metadata(installation_path)?.dev())
+    }
+
+    /// Hardlinks share permissions, so files only differing in their mode
+    /// are stored separately.
+    fn object_path(&self, digest: &str, mode: u32) -> PathBuf {
+        self.root_path
+            .join(&digest[..2])
+            .join(format!("{}-{:o}", digest, mode & 0o777))
+    }
+
+    /// Replaces `path` with a link to the stored copy of the same contents,
+    /// or adds `path` to the store if it's new. Returns whether `path` was
+    /// replaced, meaning its size is saved.
+    pub fn deduplicate(&self, path: &Path, digest: &str, mode: u32) -> Result<bool, Error> {
+        let object_path = self.object_path(digest, mode);
+
+        if object_path.exists() {
+            // Stores from before objects were read-only still have writable ones.
+            make_read_only(&object_path)?;
+            let mut linking_path = path.as_os_str().to_owned();
+            linking_path.push(".linking");
+            // Left behind by an interrupted run, it would block linking forever.
+            if fs::symlink_metadata(&linking_path).is_ok() {
+                fs::remove_file(&linking_path).with_context(|| {
+                    format!(
+                        "Failed to remove stale {}",
+                        Path::new(&linking_path).display()
+                    )
+                })?;
+            }
+            fs::hard_link(&object_path, &linking_path)?;
+            fs::rename(&linking_path, path)?;
+            return Ok(true);
+        }
+
+        if let Some(parent) = object_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        make_read_only(path)?;
+        match fs::hard_link(path, &object_path) {
+            Ok(()) => Ok(false),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+            Err(error) => Err(error).with_context(|| {
+                format!(
+                    "Failed to add {} to content store at {}, it must be on the same filesystem",
+                    path.display(),
+                    self.root_path.display()
+                )
+            }),
+        }
+    }
+
+    /// Removes objects no installation links to anymore. Returns how many
+    /// were removed and how many bytes that freed.
+    pub fn collect_garbage(&self) -> Result<(u64, u64), Error> {
+        let mut removed_objects = 0;
+        let mut freed = 0;
+        for prefix_directory in list_directory(&self.root_path)? {
+            for object_path in list_directory(&prefix_directory)? {
+                let metadata = fs::symlink_metadata(&object_path)?;
+                if metadata.is_file() && metadata.nlink() == 1 {
+                    fs::remove_file(&object_path).with_context(|| {
+                        format!("Failed to remove stored file {}", object_path.display())
+                    })?;
+                    removed_objects += 1;
+                    freed += metadata.len();
+                }
+            }
+            // Only succeeds once the prefix directory is empty.
+            let _ = fs::remove_dir(&prefix_directory);
+        }
+
+        Ok((removed_objects, freed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::test_directory::TestDirectory;
+
+    #[test]
+    fn links_identical_files_read_only() {
+        let directory = TestDirectory::create();
+        let content_store = ContentStore::new(directory.join("store"));
+        let first_path = directory.join("first.json");
+        let second_path = directory.join("second.json");
+        for path in [&first_path, &second_path] {
+            fs::write(path, "[]").unwrap();
+            fs::set_permissions(path, fs::Permissions::from_mode(0o644)).unwrap();
+        }
+
+        assert!(!content_store
+            .deduplicate(&first_path, "4f53cda1", 0o644)
+            .unwrap());
+        assert!(content_store
+            .deduplicate(&second_path, "4f53cda1", 0o644)
+            .unwrap());
+
+        let first = fs::metadata(&first_path).unwrap();
+        let second = fs::metadata(&second_path).unwrap();
+        assert_eq!(first.ino(), second.ino());
+        assert_eq!(first.nlink(), 3);
+        assert_eq!(first.permissions().mode() & 0o777, 0o444);
+    }
+
+    #[test]
+    fn links_over_a_stale_linking_file() {
+        let directory = TestDirectory::create();
+        let content_store = ContentStore::new(directory.join("store"));
+        let first_path = directory.join("first.json");
+        let second_path = directory.join("second.json");
+        fs::write(&first_path, "[]").unwrap();
+        fs::write(&second_path, "[]").unwrap();
+        fs::write(directory.join("second.json.linking"), "partial").unwrap();
+
+        content_store
+            .deduplicate(&first_path, "4f53cda1", 0o644)
+            .unwrap();
+        assert!(content_store
+            .deduplicate(&second_path, "4f53cda1", 0o644)
+            .unwrap());
+        assert!(!directory.join("second.json.linking").exists());
+        assert_eq!(
+            fs::metadata(&first_path).unwrap().ino(),
+            fs::metadata(&second_path).unwrap().ino()
+        );
+    }
+
+    #[test]
+    fn collects_objects_nothing_links_to() {
+        let directory = TestDirectory::create();
+        let content_store = ContentStore::new(directory.join("store"));
+        let path = directory.join("file");
+        fs::write(&path, "contents").unwrap();
+        content_store.deduplicate(&path, "d1b2a59f", 0o644).unwrap();
+
+        assert_eq!(content_store.collect_garbage().unwrap(), (0, 0));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(content_store.collect_garbage().unwrap(), (1, 8));
+        assert!(list_directory(&directory.join("store")).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,301 @@
+use anyhow::{anyhow, Context, Error};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::worlds::read_json;
+
+pub const OPTIONS_FILE_PATH: &str = "config/options.json";
+/// Options the game data defines on top of those built into the game.
+pub const EXTERNAL_OPTIONS_FILE_PATH: &str = "data/core/external_options.json";
+const EXTERNAL_OPTION_TYPE: &str = "EXTERNAL_OPTION";
+
+/// Entries of a `config/options.json`, an array of `{name, value}` objects
+/// that may carry more fields like `info` and `default`. Entries keep their
+/// order and extra fields when written back.
+#[derive(Debug, Clone, Default)]
+pub struct GameOptions {
+    entries: Vec<Map<String, Value>>,
+}
+
+/// The game writes every value as a string, but older files have the odd
+/// number or boolean.
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn entry_name(entry: &Map<String, Value>) -> Option<&str> {
+    entry.get("name").and_then(Value::as_str)
+}
+
+impl GameOptions {
+    pub fn options_file_path(user_data_path: &Path) -> PathBuf {
+        user_data_path.join(OPTIONS_FILE_PATH)
+    }
+
+    /// Options of the game using `user_data_path`, `None` if the game hasn't
+    /// written them there yet.
+    pub fn read(user_data_path: &Path) -> Result<Option<Self>, Error> {
+        let options_path = Self::options_file_path(user_data_path);
+        if !options_path.is_file() {
+            return Ok(None);
+        }
+
+        match read_json(&options_path)? {
+            Value::Array(entries) => Ok(Some(Self {
+                entries: entries
+                    .into_iter()
+                    .filter_map(|entry| match entry {
+                        Value::Object(entry) => Some(entry),
+                        _ => None,
+                    })
+                    .collect(),
+            })),
+            _ => Err(anyhow!(
+                "{} is not a list of options",
+                options_path.display()
+            )),
+        }
+    }
+
+    pub fn write(&self, user_data_path: &Path) -> Result<(), Error> {
+        let options_path = Self::options_file_path(user_data_path);
+        if let Some(config_path) = options_path.parent() {
+            fs::create_dir_all(config_path)
+                .with_context(|| format!("Failed to create {}", config_path.display()))?;
+        }
+        fs::write(&options_path, serde_json::to_string_pretty(&self.entries)?)
+            .with_context(|| format!("Failed to write {}", options_path.display()))
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.entries
+            .iter()
+            .find(|entry| entry_name(entry) == Some(name))
+            .and_then(|entry| entry.get("value"))
+            .map(value_to_string)
+    }
+
+    /// Changes the value of `name`, adding the option if the file doesn't
+    /// have it yet. Returns the previous value.
+    pub fn set(&mut self, name: &str, value: &str) -> Option<String> {
+        match self
+            .entries
+            .iter_mut()
+            .find(|entry| entry_name(entry) == Some(name))
+        {
+            Some(entry) => entry
+                .insert("value".to_string(), Value::String(value.to_string()))
+                .as_ref()
+                .map(value_to_string),
+            None => {
+                let mut entry = Map::new();
+                entry.insert("name".to_string(), Value::String(name.to_string()));
+                entry.insert("value".to_string(), Value::String(value.to_string()));
+                self.entries.push(entry);
+                None
+            }
+        }
+    }
+
+    /// Names and values in file order.
+    pub fn values(&self) -> Vec<(String, String)> {
+        self.entries
+            .iter()
+            .filter_map(|entry| {
+                let name = entry_name(entry)?;
+                let value = entry.get("value").map(value_to_string).unwrap_or_default();
+                Some((name.to_string(), value))
+            })
+            .collect()
+    }
+}
+
+/// Records the names of `options`, which are the ones the game that wrote
+/// them knows.
+pub fn write_option_names(
+    option_names_file_path: &Path,
+    options: &GameOptions,
+) -> Result<(), Error> {
+    let names = options
+        .values()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect::<BTreeSet<String>>();
+    if let Some(option_names_path) = option_names_file_path.parent() {
+        fs::create_dir_all(option_names_path)
+            .with_context(|| format!("Failed to create {}", option_names_path.display()))?;
+    }
+    fs::write(
+        option_names_file_path,
+        serde_json::to_string_pretty(&names)?,
+    )
+    .with_context(|| format!("Failed to write {}", option_names_file_path.display()))
+}
+
+/// Option names recorded by `write_option_names`, `None` if there are none.
+pub fn read_option_names(option_names_file_path: &Path) -> Result<Option<BTreeSet<String>>, Error> {
+    if !option_names_file_path.is_file() {
+        return Ok(None);
+    }
+
+    serde_json::from_value(read_json(option_names_file_path)?)
+        .map(Some)
+        .with_context(|| {
+            format!(
+                "{} is not a list of names",
+                option_names_file_path.display()
+            )
+        })
+}
+
+/// Names and default values of the `EXTERNAL_OPTION`s an installation's
+/// data defines, empty if it has none.
+pub fn read_external_options(installation_path: &Path) -> Result<BTreeMap<String, String>, Error> {
+    let external_options_path = installation_path.join(EXTERNAL_OPTIONS_FILE_PATH);
+    if !external_options_path.is_file() {
+        return Ok(BTreeMap::new());
+    }
+
+    match read_json(&external_options_path)? {
+        Value::Array(definitions) => Ok(definitions
+            .iter()
+            .filter(|definition| {
+                definition.get("type").and_then(Value::as_str) == Some(EXTERNAL_OPTION_TYPE)
+            })
+            .filter_map(|definition| {
+                let name = definition.get("name")?.as_str()?;
+                let value = definition.get("value").map(value_to_string);
+                Some((name.to_string(), value.unwrap_or_default()))
+            })
+            .collect()),
+        _ => Err(anyhow!(
+            "{} is not a list of definitions",
+            external_options_path.display()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::test_directory::TestDirectory;
+
+    fn write_file(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn sets_values_in_place_keeping_order_and_extra_fields() {
+        let test_directory = TestDirectory::create();
+        write_file(
+            &GameOptions::options_file_path(test_directory.path()),
+            r#"[
+                { "info": "Autosave", "default": "false", "name": "AUTOSAVE", "value": "false" },
+                { "name": "AUTO_PICKUP", "value": "true" }
+            ]"#,
+        );
+
+        let mut options = GameOptions::read(test_directory.path()).unwrap().unwrap();
+        assert_eq!(options.set("AUTOSAVE", "true"), Some("false".to_string()));
+        assert_eq!(options.set("SAFEMODE", "true"), None);
+        options.write(test_directory.path()).unwrap();
+
+        let options = GameOptions::read(test_directory.path()).unwrap().unwrap();
+        assert_eq!(
+            options.values(),
+            vec![
+                ("AUTOSAVE".to_string(), "true".to_string()),
+                ("AUTO_PICKUP".to_string(), "true".to_string()),
+                ("SAFEMODE".to_string(), "true".to_string()),
+            ]
+        );
+        assert_eq!(options.entries[0]["info"], "Autosave");
+        assert_eq!(options.entries[0]["default"], "false");
+    }
+
+    #[test]
+    fn reads_legacy_numbers_and_booleans_as_strings() {
+        let test_directory = TestDirectory::create();
+        write_file(
+            &GameOptions::options_file_path(test_directory.path()),
+            r#"[
+                { "name": "AUTOSAVE_TURNS", "value": 50 },
+                { "name": "AUTOSAVE", "value": true },
+                { "name": "NO_VALUE" },
+                { "value": "nameless" },
+                "not an entry"
+            ]"#,
+        );
+
+        let mut options = GameOptions::read(test_directory.path()).unwrap().unwrap();
+        assert_eq!(options.get("AUTOSAVE_TURNS"), Some("50".to_string()));
+        assert_eq!(options.get("NO_VALUE"), None);
+        assert_eq!(
+            options.values(),
+            vec![
+                ("AUTOSAVE_TURNS".to_string(), "50".to_string()),
+                ("AUTOSAVE".to_string(), "true".to_string()),
+                ("NO_VALUE".to_string(), String::new()),
+            ]
+        );
+        assert_eq!(options.set("AUTOSAVE", "false"), Some("true".to_string()));
+    }
+
+    #[test]
+    fn records_the_names_of_written_options() {
+        let test_directory = TestDirectory::create();
+        let option_names_file_path = test_directory.join("option_names/0.H-Curses.json");
+        assert_eq!(read_option_names(&option_names_file_path).unwrap(), None);
+
+        let mut options = GameOptions::default();
+        options.set("AUTOSAVE", "true");
+        options.set("AUTO_PICKUP", "false");
+        write_option_names(&option_names_file_path, &options).unwrap();
+        assert_eq!(
+            read_option_names(&option_names_file_path).unwrap(),
+            Some(BTreeSet::from([
+                "AUTOSAVE".to_string(),
+                "AUTO_PICKUP".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn reads_only_named_external_options() {
+        let test_directory = TestDirectory::create();
+        assert!(read_external_options(test_directory.path())
+            .unwrap()
+            .is_empty());
+
+        write_file(
+            &test_directory.join(EXTERNAL_OPTIONS_FILE_PATH),
+            r#"[
+                { "type": "EXTERNAL_OPTION", "name": "ETERNAL_SEASON", "stype": "bool", "value": false },
+                { "type": "EXTERNAL_OPTION", "name": "SPAWN_DENSITY", "value": "1.0" },
+                { "type": "EXTERNAL_OPTION", "name": "UNSET" },
+                { "type": "EXTERNAL_OPTION", "value": "nameless" },
+                { "type": "MIGRATION", "name": "NOT_AN_OPTION", "value": "1" }
+            ]"#,
+        );
+        assert_eq!(
+            read_external_options(test_directory.path()).unwrap(),
+            BTreeMap::from([
+                ("ETERNAL_SEASON".to_string(), "false".to_string()),
+                ("SPAWN_DENSITY".to_string(), "1.0".to_string()),
+                ("UNSET".to_string(), String::new()),
+            ])
+        );
+
+        write_file(
+            &test_directory.join(EXTERNAL_OPTIONS_FILE_PATH),
+            r#"{ "type": "EXTERNAL_OPTION" }"#,
+        );
+        assert!(read_external_options(test_directory.path()).is_err());
+    }
+}
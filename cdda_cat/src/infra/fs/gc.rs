@@ -0,0 +1,179 @@
+use anyhow::Error;
+use cdda_cat_data::entities::{
+    DecompressedGameDirectoryPath, ReleaseAssets, RootDownloadDirectoryPath,
+};
+use derive_more::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::cache::{is_part_file, list_directory, list_edition_directories};
+use super::staging::{sibling_path, PREVIOUS_SUFFIX, STAGING_SUFFIX};
+use super::trash::discard;
+
+#[derive(Debug, PartialEq, Clone, Display)]
+pub enum GarbageKind {
+    #[display(fmt = "unfinished download")]
+    PartFile,
+    #[display(fmt = "leftover of an interrupted install")]
+    StagingDirectory,
+    #[display(fmt = "installation missing from settings")]
+    OrphanedInstallation,
+    #[display(fmt = "directory with nothing else in it")]
+    EmptyDirectory,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Garbage {
+    pub path: PathBuf,
+    pub kind: GarbageKind,
+}
+
+/// Looks for anything below the root download directory that nothing
+/// refers to anymore. Downloaded archives are left to the archive cache.
+pub fn find_garbage(
+    root_download_directory_path: &RootDownloadDirectoryPath,
+    decompressed_game_directory_path: &DecompressedGameDirectoryPath,
+    installed_games: &ReleaseAssets,
+) -> Result<Vec<Garbage>, Error> {
+    let mut garbage = Vec::new();
+    for edition_directory in list_edition_directories(root_download_directory_path)? {
+        let installation_path = edition_directory.join(decompressed_game_directory_path.as_path());
+        let is_registered = installed_games
+            .iter()
+            .any(|asset| asset.game_edition_directory_path.as_path() == edition_directory);
+        let mut collected = Vec::new();
+
+        if !is_registered && installation_path.exists() {
+            collected.push(Garbage {
+                path: installation_path.clone(),
+                kind: GarbageKind::OrphanedInstallation,
+            });
+        }
+        for suffix in [STAGING_SUFFIX, PREVIOUS_SUFFIX] {
+            let path = sibling_path(&installation_path, suffix);
+            if path.exists() {
+                collected.push(Garbage {
+                    path,
+                    kind: GarbageKind::StagingDirectory,
+                });
+            }
+        }
+        collected.extend(
+            list_directory(&edition_directory)?
+                .into_iter()
+                .filter(|path| is_part_file(path))
+                .map(|path| Garbage {
+                    path,
+                    kind: GarbageKind::PartFile,
+                }),
+        );
+
+        let remaining_entries = list_directory(&edition_directory)?.len();
+        if !is_registered && remaining_entries == collected.len() {
+            collected.push(Garbage {
+                path: edition_directory,
+                kind: GarbageKind::EmptyDirectory,
+            });
+        }
+        garbage.extend(collected);
+    }
+
+    Ok(garbage)
+}
+
+pub fn remove_garbage(garbage: &[Garbage], use_trash: bool) -> Result<(), Error> {
+    for item in garbage {
+        remove_path(&item.path, use_trash)?;
+        println!("Removed {} ({})", item.path.display(), item.kind);
+        if item.kind == GarbageKind::EmptyDirectory {
+            // Tag and platform directories above it might be empty now too,
+            // `remove_dir` refuses to remove them otherwise.
+            for ancestor in item.path.ancestors().skip(1).take(2) {
+                if fs::remove_dir(ancestor).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn remove_path(path: &Path, use_trash: bool) -> Result<(), Error> {
+    // An empty edition directory may already be gone with its contents.
+    if !path.exists() && !path.is_symlink() {
+        return Ok(());
+    }
+    discard(path, use_trash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::test_directory::TestDirectory;
+    use cdda_cat_data::entities::{Asset, GameEditionDirectoryPath};
+    use std::str::FromStr;
+
+    fn kinds_of(garbage: &[Garbage], root_path: &Path) -> Vec<(String, GarbageKind)> {
+        garbage
+            .iter()
+            .map(|item| {
+                (
+                    item.path
+                        .strip_prefix(root_path)
+                        .unwrap()
+                        .display()
+                        .to_string(),
+                    item.kind.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_leftovers_of_unregistered_and_registered_installations() {
+        let root = TestDirectory::create();
+        for path in [
+            "linux/0.G/tiles/CDDA/save",
+            "linux/0.G/tiles/.CDDA.previous",
+            "linux/0.H/tiles/CDDA",
+        ] {
+            fs::create_dir_all(root.join(path)).unwrap();
+        }
+        fs::write(root.join("linux/0.H/tiles/cdda.tar.gz.part"), "").unwrap();
+        let registered = Asset {
+            tag: "0.G".to_string(),
+            game_edition_directory_path: GameEditionDirectoryPath::from_str(
+                root.join("linux/0.G/tiles").to_str().unwrap(),
+            )
+            .unwrap(),
+            ..Asset::default()
+        };
+
+        let garbage = find_garbage(
+            &RootDownloadDirectoryPath::new(root.path().to_str().unwrap()),
+            &DecompressedGameDirectoryPath::default(),
+            &ReleaseAssets::new(vec![registered]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            kinds_of(&garbage, root.path()),
+            [
+                (
+                    "linux/0.G/tiles/.CDDA.previous".to_string(),
+                    GarbageKind::StagingDirectory
+                ),
+                (
+                    "linux/0.H/tiles/CDDA".to_string(),
+                    GarbageKind::OrphanedInstallation
+                ),
+                (
+                    "linux/0.H/tiles/cdda.tar.gz.part".to_string(),
+                    GarbageKind::PartFile
+                ),
+                ("linux/0.H/tiles".to_string(), GarbageKind::EmptyDirectory),
+            ]
+        );
+    }
+}
@@ -0,0 +1,370 @@
+use anyhow::{anyhow, Context, Error};
+use derive_more::Display;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::worlds::read_json;
+
+pub const KEYBINDINGS_FILE_PATH: &str = "config/keybindings.json";
+/// Default bindings shipped with the game, in one file or split per menu
+/// depending on the build.
+const DEFAULT_KEYBINDINGS_PATHS: [&str; 2] = ["data/raw/keybindings.json", "data/raw/keybindings"];
+/// The game's category when an entry names none.
+const DEFAULT_CATEGORY: &str = "DEFAULTMODE";
+
+/// An action the game binds keys to, unique within its category.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Display)]
+#[display(fmt = "{} in {}", id, category)]
+pub struct ActionId {
+    pub category: String,
+    pub id: String,
+}
+
+impl ActionId {
+    fn of(entry: &Map<String, Value>) -> Option<Self> {
+        Some(Self {
+            category: entry
+                .get("category")
+                .and_then(Value::as_str)
+                .unwrap_or(DEFAULT_CATEGORY)
+                .to_string(),
+            id: entry.get("id")?.as_str()?.to_string(),
+        })
+    }
+}
+
+/// A key that ends up bound to several actions of the same category.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyClash {
+    pub category: String,
+    pub key: String,
+    pub actions: Vec<String>,
+}
+
+/// What merging bindings into another set changed.
+#[derive(Debug, Clone, Default)]
+pub struct MergeReport {
+    pub added: Vec<ActionId>,
+    /// Actions the target had bound differently.
+    pub replaced: Vec<ActionId>,
+    pub unchanged: usize,
+}
+
+impl MergeReport {
+    pub fn is_unchanged(&self) -> bool {
+        self.added.is_empty() && self.replaced.is_empty()
+    }
+}
+
+/// Entries of a `config/keybindings.json`, an array of objects naming an
+/// action by `id` and `category` with its `bindings`. Entries keep their
+/// fields when written back, so files of any build stay usable.
+#[derive(Debug, Clone, Default)]
+pub struct Keybindings {
+    entries: Vec<Map<String, Value>>,
+}
+
+/// Keys of a binding as `input_method:key`, since the same key means
+/// different things per input method.
+fn binding_keys(entry: &Map<String, Value>) -> Vec<String> {
+    entry
+        .get("bindings")
+        .and_then(Value::as_array)
+        .map(|bindings| {
+            bindings
+                .iter()
+                .filter_map(|binding| {
+                    let input_method = binding
+                        .get("input_method")
+                        .and_then(Value::as_str)
+                        .unwrap_or("keyboard");
+                    let key = match binding.get("key")? {
+                        Value::String(key) => key.to_string(),
+                        key => key.to_string(),
+                    };
+                    Some(format!("{}:{}", input_method, key))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn read_entries(path: &Path) -> Result<Vec<Map<String, Value>>, Error> {
+    match read_json(path)? {
+        Value::Array(entries) => Ok(entries
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Value::Object(entry) => Some(entry),
+                _ => None,
+            })
+            .collect()),
+        _ => Err(anyhow!("{} is not a list of keybindings", path.display())),
+    }
+}
+
+impl Keybindings {
+    pub fn keybindings_file_path(user_data_path: &Path) -> PathBuf {
+        user_data_path.join(KEYBINDINGS_FILE_PATH)
+    }
+
+    /// Custom bindings of the game using `user_data_path`, `None` if there
+    /// are none yet.
+    pub fn read(user_data_path: &Path) -> Result<Option<Self>, Error> {
+        let keybindings_path = Self::keybindings_file_path(user_data_path);
+        if !keybindings_path.is_file() {
+            return Ok(None);
+        }
+
+        Self::read_file(&keybindings_path).map(Some)
+    }
+
+    /// Reads an exported file, which is laid out like the game's own.
+    pub fn read_file(path: &Path) -> Result<Self, Error> {
+        Ok(Self {
+            entries: read_entries(path)?,
+        })
+    }
+
+    pub fn write(&self, user_data_path: &Path) -> Result<(), Error> {
+        let keybindings_path = Self::keybindings_file_path(user_data_path);
+        if let Some(config_path) = keybindings_path.parent() {
+            fs::create_dir_all(config_path)
+                .with_context(|| format!("Failed to create {}", config_path.display()))?;
+        }
+        self.write_file(&keybindings_path)
+    }
+
+    pub fn write_file(&self, path: &Path) -> Result<(), Error> {
+        fs::write(path, serde_json::to_string_pretty(&self.entries)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Takes over every binding of `other`, replacing those of the same
+    /// actions and keeping the rest.
+    pub fn merge(&mut self, other: &Keybindings) -> MergeReport {
+        let mut report = MergeReport::default();
+        for (action, other_entry) in other
+            .entries
+            .iter()
+            .filter_map(|entry| Some((ActionId::of(entry)?, entry)))
+        {
+            match self
+                .entries
+                .iter_mut()
+                .find(|entry| ActionId::of(entry).as_ref() == Some(&action))
+            {
+                Some(entry) if entry == other_entry => report.unchanged += 1,
+                Some(entry) => {
+                    *entry = other_entry.clone();
+                    report.replaced.push(action);
+                }
+                None => {
+                    self.entries.push(other_entry.clone());
+                    report.added.push(action);
+                }
+            }
+        }
+
+        report
+    }
+
+    /// Keys bound to more than one action of a category.
+    pub fn find_key_clashes(&self) -> Vec<KeyClash> {
+        let mut actions_by_key = BTreeMap::<(String, String), Vec<String>>::new();
+        for (action, entry) in self
+            .entries
+            .iter()
+            .filter_map(|entry| Some((ActionId::of(entry)?, entry)))
+        {
+            for key in binding_keys(entry) {
+                actions_by_key
+                    .entry((action.category.to_string(), key))
+                    .or_default()
+                    .push(action.id.to_string());
+            }
+        }
+
+        actions_by_key
+            .into_iter()
+            .filter(|(_, actions)| actions.len() > 1)
+            .map(|((category, key), actions)| KeyClash {
+                category,
+                key,
+                actions,
+            })
+            .collect()
+    }
+}
+
+/// Actions an installation ships default bindings for, `None` if it ships
+/// none we can read.
+pub fn read_default_actions(installation_path: &Path) -> Result<Option<BTreeSet<ActionId>>, Error> {
+    let mut default_keybindings_paths = Vec::new();
+    for default_keybindings_path in DEFAULT_KEYBINDINGS_PATHS
+        .iter()
+        .map(|path| installation_path.join(path))
+    {
+        if default_keybindings_path.is_file() {
+            default_keybindings_paths.push(default_keybindings_path);
+        } else if default_keybindings_path.is_dir() {
+            for entry in fs::read_dir(&default_keybindings_path).with_context(|| {
+                format!(
+                    "Failed to read directory {}",
+                    default_keybindings_path.display()
+                )
+            })? {
+                let path = entry?.path();
+                if path
+                    .extension()
+                    .is_some_and(|extension| extension == "json")
+                {
+                    default_keybindings_paths.push(path);
+                }
+            }
+        }
+    }
+    if default_keybindings_paths.is_empty() {
+        return Ok(None);
+    }
+
+    let mut actions = BTreeSet::new();
+    for default_keybindings_path in default_keybindings_paths {
+        actions.extend(
+            read_entries(&default_keybindings_path)?
+                .iter()
+                .filter_map(ActionId::of),
+        );
+    }
+
+    Ok(Some(actions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::test_directory::TestDirectory;
+
+    fn keybindings(json: &str) -> Keybindings {
+        Keybindings {
+            entries: serde_json::from_str(json).unwrap(),
+        }
+    }
+
+    fn action(category: &str, id: &str) -> ActionId {
+        ActionId {
+            category: category.to_string(),
+            id: id.to_string(),
+        }
+    }
+
+    fn write_file(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn merges_bindings_by_action() {
+        let mut target = keybindings(
+            r#"[
+                { "id": "pickup", "category": "DEFAULTMODE", "bindings": [{ "key": "g" }] },
+                { "id": "wait", "bindings": [{ "key": "." }] },
+                { "id": "confirm", "category": "YESNO", "bindings": [{ "key": "y" }] }
+            ]"#,
+        );
+        let report = target.merge(&keybindings(
+            r#"[
+                { "id": "pickup", "bindings": [{ "key": "," }] },
+                { "id": "wait", "bindings": [{ "key": "." }] },
+                { "id": "pickup", "category": "YESNO", "bindings": [{ "key": "p" }] },
+                { "category": "YESNO", "bindings": [{ "key": "n" }] }
+            ]"#,
+        ));
+
+        assert_eq!(report.added, vec![action("YESNO", "pickup")]);
+        assert_eq!(report.replaced, vec![action(DEFAULT_CATEGORY, "pickup")]);
+        assert_eq!(report.unchanged, 1);
+        assert!(!report.is_unchanged());
+        assert_eq!(target.len(), 4);
+        assert_eq!(binding_keys(&target.entries[0]), vec!["keyboard:,"]);
+        assert!(target.merge(&target.clone()).is_unchanged());
+    }
+
+    #[test]
+    fn finds_keys_clashing_within_a_category_and_input_method() {
+        let keybindings = keybindings(
+            r#"[
+                { "id": "pickup", "bindings": [{ "key": "g" }, { "input_method": "mouse", "key": "MOUSE_LEFT" }] },
+                { "id": "grab", "category": "DEFAULTMODE", "bindings": [{ "input_method": "keyboard", "key": "g" }] },
+                { "id": "select", "bindings": [{ "input_method": "mouse", "key": "MOUSE_LEFT" }] },
+                { "id": "go", "bindings": [{ "input_method": "gamepad", "key": "g" }] },
+                { "id": "give", "category": "INVENTORY", "bindings": [{ "key": "g" }] }
+            ]"#,
+        );
+
+        assert_eq!(
+            keybindings.find_key_clashes(),
+            vec![
+                KeyClash {
+                    category: DEFAULT_CATEGORY.to_string(),
+                    key: "keyboard:g".to_string(),
+                    actions: vec!["pickup".to_string(), "grab".to_string()],
+                },
+                KeyClash {
+                    category: DEFAULT_CATEGORY.to_string(),
+                    key: "mouse:MOUSE_LEFT".to_string(),
+                    actions: vec!["pickup".to_string(), "select".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_default_actions_from_one_file_or_a_directory() {
+        let test_directory = TestDirectory::create();
+        assert_eq!(read_default_actions(test_directory.path()).unwrap(), None);
+
+        let single_path = test_directory.join("single");
+        write_file(
+            &single_path.join("data/raw/keybindings.json"),
+            r#"[{ "id": "pickup" }, { "id": "confirm", "category": "YESNO" }]"#,
+        );
+        assert_eq!(
+            read_default_actions(&single_path).unwrap(),
+            Some(BTreeSet::from([
+                action(DEFAULT_CATEGORY, "pickup"),
+                action("YESNO", "confirm")
+            ]))
+        );
+
+        let split_path = test_directory.join("split");
+        write_file(
+            &split_path.join("data/raw/keybindings/keybindings.json"),
+            r#"[{ "id": "pickup" }]"#,
+        );
+        write_file(
+            &split_path.join("data/raw/keybindings/yesno.json"),
+            r#"[{ "id": "confirm", "category": "YESNO" }]"#,
+        );
+        write_file(
+            &split_path.join("data/raw/keybindings/README.md"),
+            "Not bindings",
+        );
+        assert_eq!(
+            read_default_actions(&split_path).unwrap(),
+            Some(BTreeSet::from([
+                action(DEFAULT_CATEGORY, "pickup"),
+                action("YESNO", "confirm")
+            ]))
+        );
+    }
+}
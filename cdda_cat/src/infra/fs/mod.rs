@@ -0,0 +1,11 @@
+pub mod app_directories;
+pub mod backup;
+pub mod cache;
+pub mod content_store;
+pub mod game_options;
+pub mod gc;
+pub mod keybindings;
+pub mod staging;
+pub mod trash;
+pub mod user_data;
+pub mod worlds;
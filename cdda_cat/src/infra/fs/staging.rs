@@ -0,0 +1,127 @@
+use anyhow::{Context, Error};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A scratch directory next to `target_path` that only replaces it once
+/// `commit` is called. Dropping it without committing throws it away and
+/// leaves whatever was at `target_path` untouched.
+#[derive(Debug)]
+pub struct StagingDirectory {
+    staging_path: PathBuf,
+    target_path: PathBuf,
+    is_committed: bool,
+}
+
+pub const STAGING_SUFFIX: &str = "staging";
+pub const PREVIOUS_SUFFIX: &str = "previous";
+
+/// `CDDA` becomes `.CDDA.<suffix>`, so it stays in the same filesystem as the
+/// target and renames are cheap.
+pub fn sibling_path(target_path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = OsString::from(".");
+    file_name.push(target_path.file_name().unwrap_or_default());
+    file_name.push(".");
+    file_name.push(suffix);
+    target_path.with_file_name(file_name)
+}
+
+fn remove_if_exists(path: &Path) -> Result<(), Error> {
+    if path.exists() {
+        fs::remove_dir_all(path)
+            .with_context(|| format!("Failed to remove leftover directory: {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+impl StagingDirectory {
+    pub fn new(target_path: &Path) -> Result<Self, Error> {
+        Self::with_staging_path(target_path, sibling_path(target_path, STAGING_SUFFIX))
+    }
+
+    /// Like `new`, but stages at `staging_path`, which has to be on the same
+    /// filesystem as `target_path`.
+    pub fn with_staging_path(target_path: &Path, staging_path: PathBuf) -> Result<Self, Error> {
+        // Leftovers of an install that got killed before it could clean up.
+        remove_if_exists(&staging_path)?;
+        fs::create_dir_all(&staging_path).with_context(|| {
+            format!(
+                "Failed to create staging directory: {}",
+                staging_path.display()
+            )
+        })?;
+
+        Ok(Self {
+            staging_path,
+            target_path: target_path.to_path_buf(),
+            is_committed: false,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.staging_path
+    }
+
+    /// Moves the staged tree into place. The previous tree is only deleted
+    /// once the new one has been renamed over it, and is put back otherwise.
+    /// Failing to delete it after that only warns, since the commit itself
+    /// went through.
+    pub fn commit(mut self) -> Result<(), Error> {
+        let previous_path = sibling_path(&self.target_path, PREVIOUS_SUFFIX);
+        remove_if_exists(&previous_path)?;
+
+        let has_previous_tree = self.target_path.exists();
+        if has_previous_tree {
+            fs::rename(&self.target_path, &previous_path).with_context(|| {
+                format!(
+                    "Failed to move previous installation out of the way: {}",
+                    self.target_path.display()
+                )
+            })?;
+        }
+
+        if let Err(error) = fs::rename(&self.staging_path, &self.target_path) {
+            if has_previous_tree {
+                fs::rename(&previous_path, &self.target_path).with_context(|| {
+                    format!(
+                        "Failed to restore previous installation, it's kept at: {}",
+                        previous_path.display()
+                    )
+                })?;
+            }
+            return Err(error).with_context(|| {
+                format!(
+                    "Failed to move staged installation into place: {}",
+                    self.target_path.display()
+                )
+            });
+        }
+        self.is_committed = true;
+
+        // The new tree is in place, so a leftover previous tree is only
+        // wasted space that the next commit removes anyway.
+        if has_previous_tree {
+            if let Err(error) = remove_if_exists(&previous_path) {
+                eprintln!("{:#}, gc will remove it later", error);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for StagingDirectory {
+    fn drop(&mut self) {
+        if !self.is_committed && self.staging_path.exists() {
+            println!("Rolling back, removing {}", self.staging_path.display());
+            if let Err(error) = fs::remove_dir_all(&self.staging_path) {
+                eprintln!(
+                    "Failed to remove staging directory {}: {}",
+                    self.staging_path.display(),
+                    error
+                );
+            }
+        }
+    }
+}
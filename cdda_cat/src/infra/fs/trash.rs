@@ -0,0 +1,196 @@
+use anyhow::{anyhow, Context, Error};
+use chrono::Local;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use super::user_data::{move_path, remove_any};
+
+/// The home trash of the freedesktop.org Trash specification.
+#[derive(Debug, Clone)]
+pub struct Trash {
+    root_path: PathBuf,
+}
+
+impl Trash {
+    /// `$XDG_DATA_HOME/Trash`, falling back to `~/.local/share/Trash`.
+    pub fn home() -> Result<Self, Error> {
+        let data_home = env::var_os("XDG_DATA_HOME")
+            .filter(|data_home| !data_home.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+            .ok_or_else(|| {
+                anyhow!("Neither XDG_DATA_HOME nor HOME is set, can't find the trash")
+            })?;
+
+        Ok(Self {
+            root_path: data_home.join("Trash"),
+        })
+    }
+
+    /// Moves `path` into the trash along with a `.trashinfo` file so file
+    /// managers can restore it. Returns where it ended up.
+    pub fn put(&self, path: &Path) -> Result<PathBuf, Error> {
+        let path = std::path::absolute(path)
+            .with_context(|| format!("Failed to find {}", path.display()))?;
+        let files_path = self.root_path.join("files");
+        let info_path = self.root_path.join("info");
+        for directory_path in [&files_path, &info_path] {
+            fs::create_dir_all(directory_path)
+                .with_context(|| format!("Failed to create {}", directory_path.display()))?;
+        }
+
+        let (trashed_name, trash_info_path) = self.reserve_name(&path, &info_path)?;
+        let trashed_path = files_path.join(&trashed_name);
+        if let Err(error) = move_path(&path, &trashed_path) {
+            let _ = fs::remove_file(&trash_info_path);
+            return Err(error);
+        }
+
+        Ok(trashed_path)
+    }
+
+    /// Claims a name no other trashed item uses by creating its `.trashinfo`
+    /// file exclusively, as the specification asks.
+    fn reserve_name(&self, path: &Path, info_path: &Path) -> Result<(OsString, PathBuf), Error> {
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| anyhow!("Refusing to trash {}", path.display()))?;
+        let trash_info = format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            percent_encode(path),
+            Local::now().format("%Y-%m-%dT%H:%M:%S")
+        );
+
+        let mut attempt = 1;
+        loop {
+            let mut trashed_name = file_name.to_owned();
+            if attempt > 1 {
+                trashed_name.push(format!(".{}", attempt));
+            }
+            let mut trash_info_name = trashed_name.clone();
+            trash_info_name.push(".trashinfo");
+            let trash_info_path = info_path.join(trash_info_name);
+
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&trash_info_path)
+            {
+                Ok(mut trash_info_file) => {
+                    trash_info_file.write_all(trash_info.as_bytes())?;
+                    return Ok((trashed_name, trash_info_path));
+                }
+                Err(error) if error.kind() == ErrorKind::AlreadyExists => attempt += 1,
+                Err(error) => {
+                    return Err(error)
+                        .with_context(|| format!("Failed to create {}", trash_info_path.display()))
+                }
+            }
+        }
+    }
+}
+
+/// Escapes `path` like a URL path, which is how `.trashinfo` files store it.
+fn percent_encode(path: &Path) -> String {
+    path.as_os_str()
+        .as_bytes()
+        .iter()
+        .map(|&byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Moves `path` to the trash if `use_trash` is set, removes it otherwise.
+pub fn discard(path: &Path, use_trash: bool) -> Result<(), Error> {
+    if use_trash {
+        let trashed_path = Trash::home()?.put(path)?;
+        println!(
+            "Moved {} to the trash at {}",
+            path.display(),
+            trashed_path.display()
+        );
+        return Ok(());
+    }
+
+    remove_any(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::test_directory::TestDirectory;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn percent_encodes_everything_but_unreserved_characters() {
+        assert_eq!(
+            percent_encode(Path::new("/home/me/CDDA 0.H/save~1_x-y.z")),
+            "/home/me/CDDA%200.H/save~1_x-y.z"
+        );
+        assert_eq!(percent_encode(Path::new("/a%b#c")), "/a%25b%23c");
+        assert_eq!(
+            percent_encode(Path::new(OsStr::from_bytes(b"/caf\xc3\xa9\xff"))),
+            "/caf%C3%A9%FF"
+        );
+    }
+
+    #[test]
+    fn numbers_names_already_in_the_trash() {
+        let directory = TestDirectory::create();
+        let trash = Trash {
+            root_path: directory.join("Trash"),
+        };
+        for (contents, path) in [("first", "one/World1"), ("second", "two/World1")] {
+            fs::create_dir_all(directory.join(path)).unwrap();
+            fs::write(directory.join(path).join("master.gsav"), contents).unwrap();
+        }
+
+        let first_path = trash.put(&directory.join("one/World1")).unwrap();
+        let second_path = trash.put(&directory.join("two/World1")).unwrap();
+
+        assert_eq!(first_path, directory.join("Trash/files/World1"));
+        assert_eq!(second_path, directory.join("Trash/files/World1.2"));
+        assert_eq!(
+            fs::read_to_string(second_path.join("master.gsav")).unwrap(),
+            "second"
+        );
+        assert!(!directory.join("two/World1").exists());
+        let trash_info =
+            fs::read_to_string(directory.join("Trash/info/World1.2.trashinfo")).unwrap();
+        assert!(trash_info.starts_with(&format!(
+            "[Trash Info]\nPath={}\nDeletionDate=",
+            percent_encode(&directory.join("two/World1"))
+        )));
+    }
+
+    #[test]
+    fn never_reuses_a_reserved_trash_info() {
+        let directory = TestDirectory::create();
+        let trash = Trash {
+            root_path: directory.join("Trash"),
+        };
+        let info_path = directory.join("Trash/info");
+        fs::create_dir_all(&info_path).unwrap();
+        // Claimed by another program that hasn't moved its file in yet.
+        fs::write(info_path.join("options.json.trashinfo"), "claimed").unwrap();
+
+        let (trashed_name, trash_info_path) = trash
+            .reserve_name(&directory.join("options.json"), &info_path)
+            .unwrap();
+
+        assert_eq!(trashed_name, "options.json.2");
+        assert_eq!(trash_info_path, info_path.join("options.json.2.trashinfo"));
+        assert_eq!(
+            fs::read_to_string(info_path.join("options.json.trashinfo")).unwrap(),
+            "claimed"
+        );
+    }
+}
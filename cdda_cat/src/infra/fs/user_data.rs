@@ -0,0 +1,519 @@
+use anyhow::{anyhow, Context, Error};
+use derive_more::Display;
+use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+use crate::infra::archive::{tar_gz, tarball};
+use crate::infra::net::download::part_filepath;
+
+use super::staging::{sibling_path, PREVIOUS_SUFFIX};
+
+/// Directories the game writes player data into, next to its launcher.
+pub const USER_DATA_DIRECTORY_NAMES: [&str; 5] =
+    ["save", "config", "templates", "memorial", "graveyard"];
+
+/// Directories shipped with the game that players also drop their own
+/// content into.
+pub const USER_CONTENT_DIRECTORY_NAMES: [&str; 3] = ["mods", "gfx", "sound"];
+
+/// Files that were copied over and ones left alone because the destination
+/// already had them.
+#[derive(Debug, Default)]
+pub struct CopyReport {
+    pub copied_files: u64,
+    pub skipped_paths: Vec<PathBuf>,
+}
+
+/// Copies `source` into `destination` recursively, keeping symlinks as they
+/// are and never replacing anything already at the destination.
+pub fn copy_recursively(
+    source: &Path,
+    destination: &Path,
+    report: &mut CopyReport,
+) -> Result<(), Error> {
+    let file_type = fs::symlink_metadata(source)
+        .with_context(|| format!("Failed to read {}", source.display()))?
+        .file_type();
+
+    if file_type.is_dir() {
+        fs::create_dir_all(destination)
+            .with_context(|| format!("Failed to create {}", destination.display()))?;
+        for entry in fs::read_dir(source)
+            .with_context(|| format!("Failed to read directory {}", source.display()))?
+        {
+            let entry = entry?;
+            copy_recursively(&entry.path(), &destination.join(entry.file_name()), report)?;
+        }
+    } else if fs::symlink_metadata(destination).is_ok() {
+        report.skipped_paths.push(destination.to_path_buf());
+    } else if file_type.is_symlink() {
+        symlink(fs::read_link(source)?, destination)
+            .with_context(|| format!("Failed to create symlink {}", destination.display()))?;
+        report.copied_files += 1;
+    } else {
+        fs::copy(source, destination).with_context(|| {
+            format!(
+                "Failed to copy {} to {}",
+                source.display(),
+                destination.display()
+            )
+        })?;
+        report.copied_files += 1;
+    }
+
+    Ok(())
+}
+
+/// Lists what an installation shipped in `USER_CONTENT_DIRECTORY_NAMES`,
+/// written next to its launcher when it gets installed.
+pub const SHIPPED_CONTENT_FILE_NAME: &str = ".cdda_cat-shipped_content.json";
+
+/// Entries right inside `USER_CONTENT_DIRECTORY_NAMES` of `installation_path`.
+pub fn list_content_entries(installation_path: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut entries = Vec::new();
+    for directory_name in USER_CONTENT_DIRECTORY_NAMES {
+        let content_directory_path = installation_path.join(directory_name);
+        if !content_directory_path.is_dir() {
+            continue;
+        }
+
+        for entry in fs::read_dir(&content_directory_path).with_context(|| {
+            format!(
+                "Failed to read directory {}",
+                content_directory_path.display()
+            )
+        })? {
+            entries.push(Path::new(directory_name).join(entry?.file_name()));
+        }
+    }
+    entries.sort();
+
+    Ok(entries)
+}
+
+/// Records the content a freshly extracted installation ships, so it can
+/// later be told apart from what the player added.
+pub fn record_shipped_content(installation_path: &Path) -> Result<(), Error> {
+    let shipped_content_path = installation_path.join(SHIPPED_CONTENT_FILE_NAME);
+    fs::write(
+        &shipped_content_path,
+        serde_json::to_string_pretty(&list_content_entries(installation_path)?)?,
+    )
+    .with_context(|| format!("Failed to write {}", shipped_content_path.display()))
+}
+
+/// Content `installation_path` shipped with, `None` for installations from
+/// before it was recorded.
+pub fn read_shipped_content(installation_path: &Path) -> Result<Option<BTreeSet<PathBuf>>, Error> {
+    let shipped_content_path = installation_path.join(SHIPPED_CONTENT_FILE_NAME);
+    if !shipped_content_path.is_file() {
+        return Ok(None);
+    }
+
+    let shipped_content = fs::read_to_string(&shipped_content_path)
+        .with_context(|| format!("Failed to read {}", shipped_content_path.display()))?;
+    serde_json::from_str(&shipped_content)
+        .map(Some)
+        .with_context(|| format!("Failed to parse {}", shipped_content_path.display()))
+}
+
+/// Entries of `USER_CONTENT_DIRECTORY_NAMES` in `installation_path` that it
+/// didn't ship with, i.e. the ones the player added. Installations without
+/// a record of their shipped content are compared against
+/// `pristine_installation_path` instead, which takes content that build
+/// dropped for the player's, and have none without it.
+pub fn find_user_added_content(
+    installation_path: &Path,
+    pristine_installation_path: Option<&Path>,
+) -> Result<Vec<PathBuf>, Error> {
+    let shipped_content = read_shipped_content(installation_path)?;
+    let mut user_added_content = Vec::new();
+    for relative_path in list_content_entries(installation_path)? {
+        let is_shipped = match (&shipped_content, pristine_installation_path) {
+            (Some(shipped_content), _) => shipped_content.contains(&relative_path),
+            (None, Some(pristine_installation_path)) => {
+                fs::symlink_metadata(pristine_installation_path.join(&relative_path)).is_ok()
+            }
+            (None, None) => true,
+        };
+        if !is_shipped {
+            user_added_content.push(relative_path);
+        }
+    }
+
+    Ok(user_added_content)
+}
+
+/// Copies saves, configuration and user-added content of an old
+/// installation into a freshly installed one.
+pub fn carry_over_user_data(
+    old_installation_path: &Path,
+    new_installation_path: &Path,
+) -> Result<CopyReport, Error> {
+    let mut report = CopyReport::default();
+    let user_data_paths = USER_DATA_DIRECTORY_NAMES
+        .iter()
+        .map(PathBuf::from)
+        .filter(|path| old_installation_path.join(path).exists())
+        .chain(find_user_added_content(
+            old_installation_path,
+            Some(new_installation_path),
+        )?);
+    for relative_path in user_data_paths {
+        copy_recursively(
+            &old_installation_path.join(&relative_path),
+            &new_installation_path.join(&relative_path),
+            &mut report,
+        )?;
+    }
+
+    Ok(report)
+}
+
+/// `USER_DATA_DIRECTORY_NAMES` in `installation_path` that hold anything.
+pub fn find_user_data(installation_path: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut user_data_paths = Vec::new();
+    for directory_name in USER_DATA_DIRECTORY_NAMES {
+        let directory_path = installation_path.join(directory_name);
+        if directory_path.is_dir()
+            && fs::read_dir(&directory_path)
+                .with_context(|| format!("Failed to read directory {}", directory_path.display()))?
+                .next()
+                .is_some()
+        {
+            user_data_paths.push(PathBuf::from(directory_name));
+        }
+    }
+
+    Ok(user_data_paths)
+}
+
+/// Packs `user_data_paths` of `installation_path` into a gzipped tarball at
+/// `archive_path`, which only shows up once it is complete.
+pub fn archive_user_data(
+    installation_path: &Path,
+    user_data_paths: &[PathBuf],
+    archive_path: &Path,
+) -> Result<(), Error> {
+    if let Some(archive_directory_path) = archive_path.parent() {
+        fs::create_dir_all(archive_directory_path)
+            .with_context(|| format!("Failed to create {}", archive_directory_path.display()))?;
+    }
+
+    let part_path = part_filepath(archive_path);
+    let archive_file = File::create(&part_path)
+        .with_context(|| format!("Failed to create {}", part_path.display()))?;
+    tarball::pack(
+        tar_gz::encoder(archive_file),
+        installation_path,
+        user_data_paths,
+    )?
+    .finish()?
+    .sync_all()?;
+    fs::rename(&part_path, archive_path)
+        .with_context(|| format!("Failed to move {} into place", archive_path.display()))?;
+
+    Ok(())
+}
+
+/// Removes a file, symlink or whole directory tree at `path`.
+pub fn remove_any(path: &Path) -> Result<(), Error> {
+    if path.is_dir() && !path.is_symlink() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+    .with_context(|| format!("Failed to remove: {}", path.display()))
+}
+
+/// Renames `from` to `to`, copying and removing it instead when they are on
+/// different filesystems.
+pub fn move_path(from: &Path, to: &Path) -> Result<(), Error> {
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == ErrorKind::CrossesDevices => copy_and_remove(from, to),
+        Err(error) => Err(error)
+            .with_context(|| format!("Failed to move {} to {}", from.display(), to.display())),
+    }
+}
+
+/// Moves `from` to a `to` that doesn't exist yet by copying it, only
+/// removing `from` once every file of it made it over.
+fn copy_and_remove(from: &Path, to: &Path) -> Result<(), Error> {
+    if fs::symlink_metadata(to).is_ok() {
+        return Err(anyhow!(
+            "Failed to move {} to {}, which already exists",
+            from.display(),
+            to.display()
+        ));
+    }
+
+    let mut report = CopyReport::default();
+    let copy_result = copy_recursively(from, to, &mut report).and_then(|()| {
+        match report.skipped_paths.first() {
+            // Something else wrote there while copying.
+            Some(skipped_path) => Err(anyhow!(
+                "{} showed up while moving {} there",
+                skipped_path.display(),
+                from.display()
+            )),
+            None => Ok(()),
+        }
+    });
+    if let Err(error) = copy_result {
+        if report.skipped_paths.is_empty() && fs::symlink_metadata(to).is_ok() {
+            remove_any(to)?;
+        }
+        return Err(error)
+            .with_context(|| format!("Failed to move {}, it was left in place", from.display()));
+    }
+
+    remove_any(from)
+}
+
+/// What to do with an entry the destination of a migration already has.
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+pub enum ConflictResolution {
+    #[display(fmt = "overwrite")]
+    Overwrite,
+    #[display(fmt = "skip")]
+    Skip,
+    #[display(fmt = "rename")]
+    Rename,
+}
+
+impl ::core::str::FromStr for ConflictResolution {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "overwrite" | "o" => Ok(ConflictResolution::Overwrite),
+            "skip" | "s" => Ok(ConflictResolution::Skip),
+            "rename" | "r" => Ok(ConflictResolution::Rename),
+            _ => Err(anyhow!(
+                "Unknown conflict resolution {}, expected overwrite, skip or rename",
+                s
+            )),
+        }
+    }
+}
+
+/// Where a migrated entry ended up, relative to the destination.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationOutcome {
+    Migrated,
+    Overwritten,
+    Skipped,
+    Renamed(PathBuf),
+}
+
+/// Worlds, configuration files and other entries right inside
+/// `USER_DATA_DIRECTORY_NAMES` of `installation_path`, which are migrated
+/// as a whole.
+pub fn list_user_data_entries(installation_path: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut entries = Vec::new();
+    for directory_name in USER_DATA_DIRECTORY_NAMES {
+        let directory_path = installation_path.join(directory_name);
+        if !directory_path.is_dir() {
+            continue;
+        }
+
+        for entry in fs::read_dir(&directory_path)
+            .with_context(|| format!("Failed to read directory {}", directory_path.display()))?
+        {
+            entries.push(Path::new(directory_name).join(entry?.file_name()));
+        }
+    }
+    entries.sort();
+
+    Ok(entries)
+}
+
+/// `World1` becomes `World1-migrated`, then `World1-migrated-2` and so on,
+/// keeping the extension of files like `options.json`.
+fn free_renamed_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default();
+    (1..)
+        .map(|counter| {
+            let mut file_name = OsString::from(stem);
+            file_name.push("-migrated");
+            if counter > 1 {
+                file_name.push(format!("-{}", counter));
+            }
+            if let Some(extension) = path.extension() {
+                file_name.push(".");
+                file_name.push(extension);
+            }
+            path.with_file_name(file_name)
+        })
+        .find(|renamed_path| fs::symlink_metadata(renamed_path).is_err())
+        .expect("Ran out of names to rename to")
+}
+
+fn transfer(source: &Path, destination: &Path, move_entry: bool) -> Result<(), Error> {
+    if let Some(parent_path) = destination.parent() {
+        fs::create_dir_all(parent_path)
+            .with_context(|| format!("Failed to create {}", parent_path.display()))?;
+    }
+    if move_entry {
+        move_path(source, destination)
+    } else {
+        copy_recursively(source, destination, &mut CopyReport::default())
+    }
+}
+
+/// Copies, or moves with `move_entry`, `relative_path` of `source_path` into
+/// `destination_path`, settling a clash with `resolution`. An overwritten
+/// entry is only removed once its replacement is in place.
+pub fn migrate_entry(
+    source_path: &Path,
+    destination_path: &Path,
+    relative_path: &Path,
+    resolution: ConflictResolution,
+    move_entry: bool,
+) -> Result<MigrationOutcome, Error> {
+    let source = source_path.join(relative_path);
+    let destination = destination_path.join(relative_path);
+    if fs::symlink_metadata(&destination).is_err() {
+        transfer(&source, &destination, move_entry)?;
+        return Ok(MigrationOutcome::Migrated);
+    }
+
+    match resolution {
+        ConflictResolution::Skip => Ok(MigrationOutcome::Skipped),
+        ConflictResolution::Rename => {
+            let renamed_path = free_renamed_path(&destination);
+            transfer(&source, &renamed_path, move_entry)?;
+            Ok(MigrationOutcome::Renamed(
+                renamed_path
+                    .strip_prefix(destination_path)
+                    .unwrap_or(&renamed_path)
+                    .to_path_buf(),
+            ))
+        }
+        ConflictResolution::Overwrite => {
+            let previous_path = sibling_path(&destination, PREVIOUS_SUFFIX);
+            if fs::symlink_metadata(&previous_path).is_ok() {
+                remove_any(&previous_path)?;
+            }
+            fs::rename(&destination, &previous_path).with_context(|| {
+                format!("Failed to move {} out of the way", destination.display())
+            })?;
+            if let Err(error) = transfer(&source, &destination, move_entry) {
+                if fs::symlink_metadata(&destination).is_ok() {
+                    remove_any(&destination)?;
+                }
+                fs::rename(&previous_path, &destination).with_context(|| {
+                    format!(
+                        "Failed to put {} back after: {}",
+                        destination.display(),
+                        error
+                    )
+                })?;
+                return Err(error);
+            }
+            remove_any(&previous_path)?;
+            Ok(MigrationOutcome::Overwritten)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::test_directory::TestDirectory;
+
+    fn write_files(directory: &TestDirectory, paths: &[&str]) {
+        for path in paths {
+            fs::create_dir_all(directory.join(path).parent().unwrap()).unwrap();
+            fs::write(directory.join(path), path).unwrap();
+        }
+    }
+
+    #[test]
+    fn carries_over_only_content_the_old_build_did_not_ship() {
+        let directory = TestDirectory::create();
+        write_files(
+            &directory,
+            &[
+                "old/mods/Aftershock/modinfo.json",
+                "old/mods/Dropped/modinfo.json",
+                "old/gfx/UltimateCataclysm/tileset.txt",
+                "new/mods/Aftershock/modinfo.json",
+                "new/gfx/UltimateCataclysm/tileset.txt",
+            ],
+        );
+        record_shipped_content(&directory.join("old")).unwrap();
+        record_shipped_content(&directory.join("new")).unwrap();
+        write_files(
+            &directory,
+            &["old/mods/MyMod/modinfo.json", "old/save/World1/master.gsav"],
+        );
+
+        carry_over_user_data(&directory.join("old"), &directory.join("new")).unwrap();
+
+        assert!(directory.join("new/mods/MyMod/modinfo.json").is_file());
+        assert!(directory.join("new/save/World1/master.gsav").is_file());
+        assert!(!directory.join("new/mods/Dropped").exists());
+    }
+
+    #[test]
+    fn compares_unrecorded_installations_against_the_new_build() {
+        let directory = TestDirectory::create();
+        write_files(
+            &directory,
+            &[
+                "old/mods/Aftershock/modinfo.json",
+                "old/sound/MyPack/soundset.json",
+                "new/mods/Aftershock/modinfo.json",
+            ],
+        );
+
+        assert_eq!(
+            find_user_added_content(&directory.join("old"), Some(&directory.join("new"))).unwrap(),
+            vec![PathBuf::from("sound/MyPack")]
+        );
+        assert!(find_user_added_content(&directory.join("old"), None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn copies_and_removes_across_filesystems() {
+        let directory = TestDirectory::create();
+        fs::create_dir_all(directory.join("from/World1")).unwrap();
+        fs::write(directory.join("from/World1/player.sav"), "saved").unwrap();
+
+        copy_and_remove(&directory.join("from"), &directory.join("to")).unwrap();
+
+        assert!(!directory.join("from").exists());
+        assert_eq!(
+            fs::read_to_string(directory.join("to/World1/player.sav")).unwrap(),
+            "saved"
+        );
+    }
+
+    #[test]
+    fn keeps_the_source_when_the_destination_exists() {
+        let directory = TestDirectory::create();
+        for (path, contents) in [("from/player.sav", "new"), ("to/player.sav", "old")] {
+            fs::create_dir_all(directory.join(path).parent().unwrap()).unwrap();
+            fs::write(directory.join(path), contents).unwrap();
+        }
+
+        assert!(copy_and_remove(&directory.join("from"), &directory.join("to")).is_err());
+
+        assert_eq!(
+            fs::read_to_string(directory.join("from/player.sav")).unwrap(),
+            "new"
+        );
+        assert_eq!(
+            fs::read_to_string(directory.join("to/player.sav")).unwrap(),
+            "old"
+        );
+    }
+}
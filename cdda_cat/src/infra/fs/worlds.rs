@@ -0,0 +1,379 @@
+use anyhow::{anyhow, Context, Error};
+use base64::alphabet;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig};
+use base64::engine::DecodePaddingMode;
+use base64::Engine;
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use super::backup::{list_worlds, newest_modification_time, world_path};
+use super::staging::{sibling_path, StagingDirectory, STAGING_SUFFIX};
+use super::user_data::{copy_recursively, CopyReport};
+
+pub const MODS_FILE_NAME: &str = "mods.json";
+pub const WORLD_OPTIONS_FILE_NAME: &str = "worldoptions.json";
+const CHARACTER_SAVE_EXTENSION: &str = ".sav";
+
+/// World options worth a glance without opening the game.
+pub const KEY_WORLD_OPTIONS: [&str; 8] = [
+    "CITY_SIZE",
+    "CITY_SPACING",
+    "SPAWN_DENSITY",
+    "ITEM_SPAWNRATE",
+    "NPC_DENSITY",
+    "MONSTER_EVOLUTION_SLOWDOWN",
+    "ETERNAL_SEASON",
+    "WORLD_END",
+];
+
+/// What a world directory in `save/` holds, as far as we can tell without
+/// the game.
+#[derive(Debug, Clone)]
+pub struct WorldSummary {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    /// Newest modification time of its files, in seconds.
+    pub modified: u64,
+    /// `None` when `mods.json` is missing or unreadable.
+    pub mods: Option<Vec<String>>,
+    /// Values of `KEY_WORLD_OPTIONS` the world sets, in that order.
+    pub options: Vec<(String, String)>,
+    pub characters: Vec<String>,
+}
+
+/// `world` itself, once it is known to be a plain directory name.
+pub fn world_name(world: &str) -> Result<&str, Error> {
+    world_path(world)?;
+    Ok(world)
+}
+
+/// Total size of the files below `path`.
+pub fn directory_size(path: &Path) -> Result<u64, Error> {
+    let metadata =
+        fs::symlink_metadata(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    let mut size = 0;
+    for entry in fs::read_dir(path)
+        .with_context(|| format!("Failed to read directory {}", path.display()))?
+    {
+        size += directory_size(&entry?.path())?;
+    }
+
+    Ok(size)
+}
+
+/// The game names character saves `#<name in base64>.sav`.
+pub fn decode_character_name(file_name: &str) -> Option<String> {
+    let encoded = file_name
+        .strip_prefix('#')?
+        .strip_suffix(CHARACTER_SAVE_EXTENSION)?;
+    let config =
+        GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+    [alphabet::STANDARD, alphabet::URL_SAFE]
+        .iter()
+        .find_map(|alphabet| GeneralPurpose::new(alphabet, config).decode(encoded).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+}
+
+pub fn read_json(path: &Path) -> Result<Value, Error> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Mod ids of `mods.json`, a plain array of strings.
+pub fn read_world_mods(world_path: &Path) -> Result<Vec<String>, Error> {
+    let mods_path = world_path.join(MODS_FILE_NAME);
+    match read_json(&mods_path)? {
+        Value::Array(mods) => Ok(mods
+            .iter()
+            .filter_map(|mod_id| mod_id.as_str().map(str::to_string))
+            .collect()),
+        _ => Err(anyhow!("{} is not a list of mods", mods_path.display())),
+    }
+}
+
+/// Strings without their quotes, anything else as JSON.
+fn option_value_to_string(value: &Value) -> String {
+    value
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| value.to_string())
+}
+
+/// Every option of `worldoptions.json`, which is an array of `{name, value}`
+/// entries, or a plain object in saves of old builds.
+pub fn read_world_options(world_path: &Path) -> Result<Vec<(String, String)>, Error> {
+    let options_path = world_path.join(WORLD_OPTIONS_FILE_NAME);
+    match read_json(&options_path)? {
+        Value::Array(options) => Ok(options
+            .iter()
+            .filter_map(|option| {
+                Some((
+                    option.get("name")?.as_str()?.to_string(),
+                    option_value_to_string(option.get("value")?),
+                ))
+            })
+            .collect()),
+        Value::Object(options) => Ok(options
+            .iter()
+            .map(|(name, value)| (name.to_string(), option_value_to_string(value)))
+            .collect()),
+        _ => Err(anyhow!(
+            "{} is not a list of options",
+            options_path.display()
+        )),
+    }
+}
+
+/// Characters saved in the world directory at `world_path`, sorted by name.
+pub fn list_characters(world_path: &Path) -> Result<Vec<String>, Error> {
+    let mut characters = Vec::new();
+    for entry in fs::read_dir(world_path)
+        .with_context(|| format!("Failed to read directory {}", world_path.display()))?
+    {
+        let file_name = entry?.file_name().to_string_lossy().to_string();
+        if file_name.starts_with('#') && file_name.ends_with(CHARACTER_SAVE_EXTENSION) {
+            characters.push(decode_character_name(&file_name).unwrap_or(file_name));
+        }
+    }
+    characters.sort();
+
+    Ok(characters)
+}
+
+/// Newest save format of the character saves in `world_path`, which the
+/// game writes as a `# version <number>` first line.
+pub fn read_save_version(world_path: &Path) -> Result<Option<u32>, Error> {
+    let mut newest = None;
+    for entry in fs::read_dir(world_path)
+        .with_context(|| format!("Failed to read directory {}", world_path.display()))?
+    {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if !(file_name.starts_with('#') && file_name.ends_with(CHARACTER_SAVE_EXTENSION)) {
+            continue;
+        }
+
+        let mut first_line = String::new();
+        BufReader::new(File::open(entry.path())?)
+            .take(64)
+            .read_line(&mut first_line)
+            .ok();
+        let version = first_line
+            .trim()
+            .strip_prefix("# version ")
+            .and_then(|version| version.parse().ok());
+        newest = newest.max(version);
+    }
+
+    Ok(newest)
+}
+
+pub fn summarize_world(save_path: &Path, name: &str) -> Result<WorldSummary, Error> {
+    let path = save_path.join(name);
+    let options = read_world_options(&path).unwrap_or_default();
+    let options = KEY_WORLD_OPTIONS
+        .iter()
+        .filter_map(|key| options.iter().find(|(name, _)| name == key).cloned())
+        .collect();
+
+    Ok(WorldSummary {
+        name: name.to_string(),
+        size: directory_size(&path)?,
+        modified: newest_modification_time(&path)?,
+        mods: read_world_mods(&path).ok(),
+        options,
+        characters: list_characters(&path)?,
+        path,
+    })
+}
+
+/// Summaries of every world in `save_path`, sorted by name.
+pub fn summarize_worlds(save_path: &Path) -> Result<Vec<WorldSummary>, Error> {
+    list_worlds(save_path)?
+        .iter()
+        .map(|world| summarize_world(save_path, world))
+        .collect()
+}
+
+/// Replaces the mod list of the world at `world_path`.
+pub fn write_world_mods(world_path: &Path, mods: &[String]) -> Result<(), Error> {
+    let mods_path = world_path.join(MODS_FILE_NAME);
+    fs::write(&mods_path, serde_json::to_string_pretty(mods)?)
+        .with_context(|| format!("Failed to write {}", mods_path.display()))
+}
+
+/// Copies the world `from` of `save_path` to a new world `to`, which only
+/// shows up once the copy is complete. `mods` replaces the copy's mod list.
+pub fn clone_world(
+    save_path: &Path,
+    from: &str,
+    to: &str,
+    mods: Option<&[String]>,
+) -> Result<PathBuf, Error> {
+    let source_path = save_path.join(world_name(from)?);
+    let target_path = save_path.join(world_name(to)?);
+    if !source_path.is_dir() {
+        return Err(anyhow!(
+            "There is no world {} in {}",
+            from,
+            save_path.display()
+        ));
+    }
+    if fs::symlink_metadata(&target_path).is_ok() {
+        return Err(anyhow!(
+            "There already is a world {} in {}",
+            to,
+            save_path.display()
+        ));
+    }
+
+    // Staged next to `save/` instead of in it, where the game would take an
+    // interrupted copy for a world.
+    let staging_directory = StagingDirectory::with_staging_path(
+        &target_path,
+        sibling_path(save_path, &format!("{}.{}", to, STAGING_SUFFIX)),
+    )?;
+    copy_recursively(
+        &source_path,
+        staging_directory.path(),
+        &mut CopyReport::default(),
+    )?;
+    if let Some(mods) = mods {
+        write_world_mods(staging_directory.path(), mods)?;
+    }
+    staging_directory.commit()?;
+
+    Ok(target_path)
+}
+
+pub fn rename_world(save_path: &Path, from: &str, to: &str) -> Result<PathBuf, Error> {
+    let source_path = save_path.join(world_name(from)?);
+    let target_path = save_path.join(world_name(to)?);
+    if !source_path.is_dir() {
+        return Err(anyhow!(
+            "There is no world {} in {}",
+            from,
+            save_path.display()
+        ));
+    }
+    if fs::symlink_metadata(&target_path).is_ok() {
+        return Err(anyhow!(
+            "There already is a world {} in {}",
+            to,
+            save_path.display()
+        ));
+    }
+
+    fs::rename(&source_path, &target_path).with_context(|| {
+        format!(
+            "Failed to rename {} to {}",
+            source_path.display(),
+            target_path.display()
+        )
+    })?;
+
+    Ok(target_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::test_directory::TestDirectory;
+
+    #[test]
+    fn decodes_character_names_of_either_base64_alphabet() {
+        assert_eq!(
+            decode_character_name("#SGVsZ2E=.sav"),
+            Some("Helga".to_string())
+        );
+        // Unpadded, and with `_` from the URL safe alphabet.
+        assert_eq!(
+            decode_character_name("#Pz8_Pw.sav"),
+            Some("????".to_string())
+        );
+        assert_eq!(decode_character_name("SGVsZ2E=.sav"), None);
+        assert_eq!(decode_character_name("#SGVsZ2E=.log"), None);
+        assert_eq!(decode_character_name("#not base64!.sav"), None);
+    }
+
+    #[test]
+    fn reads_world_options_of_both_formats() {
+        let directory = TestDirectory::create();
+        fs::write(
+            directory.join(WORLD_OPTIONS_FILE_NAME),
+            r#"[
+                {"info": "City size", "name": "CITY_SIZE", "value": "8"},
+                {"name": "ETERNAL_SEASON", "value": false},
+                {"name": "NO_VALUE"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            read_world_options(directory.path()).unwrap(),
+            [
+                ("CITY_SIZE".to_string(), "8".to_string()),
+                ("ETERNAL_SEASON".to_string(), "false".to_string())
+            ]
+        );
+
+        fs::write(
+            directory.join(WORLD_OPTIONS_FILE_NAME),
+            r#"{"CITY_SIZE": "4", "SPAWN_DENSITY": 1.5}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            read_world_options(directory.path()).unwrap(),
+            [
+                ("CITY_SIZE".to_string(), "4".to_string()),
+                ("SPAWN_DENSITY".to_string(), "1.5".to_string())
+            ]
+        );
+
+        fs::write(directory.join(WORLD_OPTIONS_FILE_NAME), "8").unwrap();
+        assert!(read_world_options(directory.path()).is_err());
+    }
+
+    #[test]
+    fn reads_the_newest_save_version_of_character_saves() {
+        let directory = TestDirectory::create();
+        assert_eq!(read_save_version(directory.path()).unwrap(), None);
+
+        for (file_name, contents) in [
+            ("#SGVsZ2E=.sav", "# version 33\n{}"),
+            ("#Qm9i.sav", "# version 35\n{}"),
+            ("#T2xk.sav", "{}"),
+            // Not a character save, whatever its first line says.
+            ("master.gsav", "# version 99\n{}"),
+        ] {
+            fs::write(directory.join(file_name), contents).unwrap();
+        }
+
+        assert_eq!(read_save_version(directory.path()).unwrap(), Some(35));
+    }
+
+    #[test]
+    fn clones_worlds_without_staging_inside_save() {
+        let directory = TestDirectory::create();
+        let save_path = directory.join("save");
+        fs::create_dir_all(save_path.join("World1")).unwrap();
+        fs::write(save_path.join("World1/master.gsav"), "saved").unwrap();
+        // Left behind by a clone from before staging moved out of `save/`.
+        fs::create_dir_all(save_path.join(".World3.staging")).unwrap();
+
+        let clone_path =
+            clone_world(&save_path, "World1", "World2", Some(&["dda".to_string()])).unwrap();
+
+        assert_eq!(clone_path, save_path.join("World2"));
+        assert_eq!(read_world_mods(&clone_path).unwrap(), ["dda"]);
+        assert_eq!(list_worlds(&save_path).unwrap(), ["World1", "World2"]);
+        assert!(!directory.join(".save.World2.staging").exists());
+    }
+}
@@ -0,0 +1,55 @@
+use anyhow::{anyhow, Error};
+use nix::libc::c_int;
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static IS_INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static IS_INTERRUPTION_REPORTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn flag_interruption(_: c_int) {
+    IS_INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Keeps Ctrl-C turned into a flag while alive. Dropping it puts back
+/// whatever handled Ctrl-C before, so it stops the process again.
+#[must_use = "Ctrl-C is only watched while the guard is alive"]
+pub struct CtrlCGuard {
+    previous_action: Option<SigAction>,
+}
+
+impl Drop for CtrlCGuard {
+    fn drop(&mut self) {
+        if let Some(previous_action) = &self.previous_action {
+            // Restoring a handler we got from `sigaction` itself.
+            let _ = unsafe { sigaction(Signal::SIGINT, previous_action) };
+        }
+    }
+}
+
+/// Turns Ctrl-C into a flag that long running work polls with `check`, so it
+/// gets the chance to roll back instead of dying halfway. Only lasts as long
+/// as the returned guard.
+pub fn watch_for_ctrl_c() -> CtrlCGuard {
+    IS_INTERRUPTED.store(false, Ordering::SeqCst);
+    IS_INTERRUPTION_REPORTED.store(false, Ordering::SeqCst);
+    let action = SigAction::new(
+        SigHandler::Handler(flag_interruption),
+        SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+    // The handler only stores to an atomic, which is signal safe.
+    let previous_action = unsafe { sigaction(Signal::SIGINT, &action) }.ok();
+
+    CtrlCGuard { previous_action }
+}
+
+pub fn check() -> Result<(), Error> {
+    if IS_INTERRUPTED.load(Ordering::SeqCst) {
+        if !IS_INTERRUPTION_REPORTED.swap(true, Ordering::SeqCst) {
+            eprintln!("\nInterrupted! Cleaning up..");
+        }
+        Err(anyhow!("Interrupted by user"))
+    } else {
+        Ok(())
+    }
+}
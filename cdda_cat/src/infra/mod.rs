@@ -1,3 +1,8 @@
 pub mod archive;
+pub mod fs;
+pub mod interrupt;
 pub mod net;
+pub mod processes;
+#[cfg(test)]
+pub mod test_directory;
 pub mod ui;
@@ -0,0 +1,101 @@
+use std::cmp::min;
+use std::io::{self, Read};
+use tokio::sync::mpsc::Receiver;
+
+/// Chunks of a download in flight. An empty chunk marks the end of a
+/// complete download, anything else ending the channel is a failure.
+pub type Chunk = io::Result<Vec<u8>>;
+
+/// Blocking `Read` over chunks sent by an async download task, so the
+/// synchronous decompressors can consume a download while it happens. Blocks
+/// its thread while waiting, so only read it from a blocking task.
+pub struct ChannelReader {
+    receiver: Receiver<Chunk>,
+    chunk: Vec<u8>,
+    position: usize,
+    is_finished: bool,
+}
+
+impl ChannelReader {
+    pub fn new(receiver: Receiver<Chunk>) -> Self {
+        Self {
+            receiver,
+            chunk: Vec::new(),
+            position: 0,
+            is_finished: false,
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            if self.is_finished {
+                return Ok(0);
+            }
+            match self.receiver.blocking_recv() {
+                Some(Ok(chunk)) if chunk.is_empty() => self.is_finished = true,
+                Some(Ok(chunk)) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                Some(Err(error)) => return Err(error),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Download stopped before it was complete",
+                    ))
+                }
+            }
+        }
+
+        let length = min(buf.len(), self.chunk.len() - self.position);
+        buf[..length].copy_from_slice(&self.chunk[self.position..self.position + length]);
+        self.position += length;
+
+        Ok(length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use tokio::sync::mpsc;
+
+    fn read_sent(chunks: Vec<Chunk>) -> io::Result<Vec<u8>> {
+        let (sender, receiver) = mpsc::channel(1);
+        let sending = thread::spawn(move || {
+            for chunk in chunks {
+                if sender.blocking_send(chunk).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut bytes = Vec::new();
+        let result = ChannelReader::new(receiver).read_to_end(&mut bytes);
+        sending.join().unwrap();
+        result.map(|_| bytes)
+    }
+
+    #[test]
+    fn reads_chunks_until_the_end_marker() {
+        let bytes = read_sent(vec![
+            Ok(b"cata".to_vec()),
+            Ok(b"clysm".to_vec()),
+            Ok(Vec::new()),
+        ])
+        .unwrap();
+        assert_eq!(bytes, b"cataclysm");
+    }
+
+    #[test]
+    fn fails_when_the_download_stops_early() {
+        let error = read_sent(vec![Ok(b"cata".to_vec())]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+
+        let error =
+            read_sent(vec![Ok(b"cata".to_vec()), Err(io::Error::other("reset"))]).unwrap_err();
+        assert_eq!(error.to_string(), "reset");
+    }
+}
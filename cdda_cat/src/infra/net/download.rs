@@ -1,11 +1,17 @@
-use std::fs::File;
-use std::io::Write;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
 use std::{cmp::min, path::PathBuf};
 
-use anyhow::{anyhow, Error};
+use anyhow::{anyhow, Context, Error};
 use futures_util::StreamExt;
 use indicatif::ProgressBar;
 use reqwest::{Client, Response};
+use tokio::sync::mpsc::Sender;
+
+use super::channel_reader::Chunk;
+use crate::infra::interrupt;
 
 #[derive(Debug, Default, Clone)]
 pub struct DownloadInfo {
@@ -33,13 +39,89 @@ impl DownloadInfo {
         let total_length = response
             .content_length()
             .ok_or(anyhow!("Failed to get content length from '{}'", &self.url))?;
-        let mut file = File::create(&self.filepath).or(Err(anyhow!(
+        let part_filepath = part_filepath(&self.filepath);
+        let mut file = File::create(&part_filepath).or(Err(anyhow!(
             "Failed to create file '{}'",
-            self.filepath.display()
+            part_filepath.display()
         )))?;
 
-        stream_download(&mut file, progress_bar, total_length, response).await
+        stream_download(&mut file, progress_bar, total_length, response).await?;
+        fs::rename(&part_filepath, &self.filepath).with_context(|| {
+            format!(
+                "Failed to move finished download to '{}'",
+                self.filepath.display()
+            )
+        })
+    }
+}
+
+/// Where a file is written to until its download completes.
+pub fn part_filepath(filepath: &Path) -> PathBuf {
+    let mut file_name = OsString::from(filepath.file_name().unwrap_or_default());
+    file_name.push(".part");
+    filepath.with_file_name(file_name)
+}
+
+impl DownloadInfo {
+    /// Sends the download chunk by chunk to `sender`, optionally keeping a
+    /// copy at `filepath` once the download completes.
+    pub async fn stream_to_channel(
+        &self,
+        progress_bar: &ProgressBar,
+        response: Response,
+        sender: Sender<Chunk>,
+        keep_file: bool,
+    ) -> Result<(), Error> {
+        let part_filepath = part_filepath(&self.filepath);
+        let mut tee_file = keep_file
+            .then(|| File::create(&part_filepath))
+            .transpose()
+            .with_context(|| format!("Failed to create file '{}'", part_filepath.display()))?;
+
+        match stream_chunks(&sender, &mut tee_file, progress_bar, response).await {
+            Ok(()) => {
+                // The receiving end might have stopped reading already once
+                // it found everything it needed.
+                let _ = sender.send(Ok(Vec::new())).await;
+                if tee_file.is_some() {
+                    fs::rename(&part_filepath, &self.filepath)?;
+                }
+                Ok(())
+            }
+            Err(error) => {
+                if tee_file.is_some() {
+                    let _ = fs::remove_file(&part_filepath);
+                }
+                let _ = sender
+                    .send(Err(io::Error::other(format!("{:#}", error))))
+                    .await;
+                Err(error)
+            }
+        }
+    }
+}
+
+async fn stream_chunks(
+    sender: &Sender<Chunk>,
+    tee_file: &mut Option<File>,
+    progress_bar: &ProgressBar,
+    response: Response,
+) -> Result<(), Error> {
+    let mut stream = response.bytes_stream();
+    while let Some(item) = stream.next().await {
+        interrupt::check()?;
+        let chunk = item.or(Err(anyhow!("Error while downloading file")))?;
+        if let Some(file) = tee_file {
+            file.write_all(&chunk)
+                .or(Err(anyhow!("Error while writing to file")))?;
+        }
+        sender.send(Ok(chunk.to_vec())).await.or(Err(anyhow!(
+            "Stopped downloading since nothing reads it anymore"
+        )))?;
+        progress_bar.inc(chunk.len() as u64);
     }
+
+    Ok(())
 }
 
 async fn stream_download(
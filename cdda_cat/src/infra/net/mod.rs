@@ -1 +1,2 @@
+pub mod channel_reader;
 pub mod download;
@@ -0,0 +1,41 @@
+use anyhow::{Context, Error};
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// Ids of running processes whose executable lives below `path`, or that
+/// were given a path below it on their command line, like the game started
+/// from an installation or with `--userdir` pointing into it.
+pub fn find_processes_using(path: &Path) -> Result<Vec<u32>, Error> {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let mut process_ids = Vec::new();
+    for entry in fs::read_dir("/proc").context("Failed to list running processes")? {
+        let entry = entry?;
+        let process_id = match entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        {
+            Some(process_id) => process_id,
+            None => continue,
+        };
+        if process_id == std::process::id() {
+            continue;
+        }
+
+        // Processes of other users, or ones that just exited, can't be read.
+        let is_executable_inside = fs::read_link(entry.path().join("exe"))
+            .is_ok_and(|executable_path| executable_path.starts_with(&path));
+        let is_argument_inside = fs::read(entry.path().join("cmdline")).is_ok_and(|cmdline| {
+            cmdline
+                .split(|byte| *byte == 0)
+                .any(|argument| Path::new(OsStr::from_bytes(argument)).starts_with(&path))
+        });
+        if is_executable_inside || is_argument_inside {
+            process_ids.push(process_id);
+        }
+    }
+
+    Ok(process_ids)
+}
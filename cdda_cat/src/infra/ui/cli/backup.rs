@@ -0,0 +1,240 @@
+use anyhow::{anyhow, Error};
+use cdda_cat_data::entities::InstallationId;
+use cdda_cat_lib::installation_manager::AppSettings;
+use chrono::Local;
+use indicatif::HumanBytes;
+use std::path::{Path, PathBuf};
+
+use crate::infra::fs::app_directories::AppDirectories;
+use crate::infra::fs::backup::{
+    find_newer_worlds, inspect, list_worlds, newest_modification_time, restore, Backup,
+    BackupStore, SAVE_DIRECTORY_NAME,
+};
+
+use super::location::{UserDataLocation, USER_DIRECTORY_PREFIX};
+
+#[derive(Debug, Clone)]
+pub enum BackupAction {
+    Create {
+        location: UserDataLocation,
+        /// All of `save/` when unset.
+        world: Option<String>,
+    },
+    List {
+        location: Option<UserDataLocation>,
+    },
+    Restore {
+        archive_path: PathBuf,
+        location: UserDataLocation,
+        force: bool,
+    },
+    Prune {
+        location: Option<UserDataLocation>,
+        keep: Option<usize>,
+    },
+}
+
+/// `installations/<tag>-<edition>` reads as `<tag>-<edition>`, user
+/// directories as `userdir:<profile>`.
+fn describe_location(location_path: &Path) -> String {
+    match (
+        location_path.parent().and_then(Path::to_str),
+        location_path.file_name(),
+    ) {
+        (Some("userdirs"), Some(profile)) => {
+            format!("{}{}", USER_DIRECTORY_PREFIX, profile.to_string_lossy())
+        }
+        (_, Some(name)) => name.to_string_lossy().to_string(),
+        _ => location_path.display().to_string(),
+    }
+}
+
+fn print_backup(backup: &Backup) {
+    println!(
+        "{}  {:>10}  {}  {}  {}",
+        backup.created.format("%Y-%m-%d %H:%M:%S"),
+        HumanBytes(backup.size).to_string(),
+        describe_location(&backup.location_path),
+        backup.world.as_deref().unwrap_or("(all worlds)"),
+        backup.path.display()
+    );
+}
+
+/// Archives `world`, or every world, of `location` and rotates out backups
+/// beyond the configured retention count.
+pub fn back_up(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    location: &UserDataLocation,
+    world: Option<&str>,
+) -> Result<PathBuf, Error> {
+    let base_path = location.resolve(settings, app_directories)?;
+    let backup_store = BackupStore::new(app_directories.backups_path());
+    let archive_path = backup_store.create(&location.backup_directory_path(), &base_path, world)?;
+    println!(
+        "Backed up {} of {} to {}",
+        world.unwrap_or("all worlds"),
+        location,
+        archive_path.display()
+    );
+
+    let removed = backup_store.prune(
+        Some(&location.backup_directory_path()),
+        settings.backup_policy.keep,
+    )?;
+    if !removed.is_empty() {
+        println!("Rotated out {} old backups.", removed.len());
+    }
+
+    Ok(archive_path)
+}
+
+/// Backs up only the worlds of `location` that changed since their newest
+/// backup, so doing it before every launch stays fast.
+pub fn back_up_changed_worlds(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    location: &UserDataLocation,
+) -> Result<usize, Error> {
+    let save_path = location
+        .resolve(settings, app_directories)?
+        .join(SAVE_DIRECTORY_NAME);
+    let backup_store = BackupStore::new(app_directories.backups_path());
+
+    let mut backed_up = 0;
+    for world in list_worlds(&save_path)? {
+        let newest_backup = backup_store.newest(&location.backup_directory_path(), Some(&world))?;
+        let backed_up_at = newest_backup.and_then(|backup| {
+            backup
+                .created
+                .and_local_timezone(Local)
+                .earliest()
+                .map(|created| created.timestamp())
+        });
+        // Changes within the second of the last backup count as newer.
+        let is_unchanged = match backed_up_at {
+            Some(backed_up_at) => {
+                (newest_modification_time(&save_path.join(&world))? as i64) < backed_up_at
+            }
+            None => false,
+        };
+        if !is_unchanged {
+            back_up(settings, app_directories, location, Some(&world))?;
+            backed_up += 1;
+        }
+    }
+
+    Ok(backed_up)
+}
+
+/// Backs up changed worlds of `installation` before its directory gets
+/// changed or removed, unless the settings turned that off.
+pub fn back_up_before_changing(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    installation: &InstallationId,
+) -> Result<(), Error> {
+    if settings.find_installation(installation).is_none() {
+        return Ok(());
+    }
+
+    back_up_location_before_changing(
+        settings,
+        app_directories,
+        &UserDataLocation::Installation(installation.clone()),
+    )
+}
+
+/// Like `back_up_before_changing`, for any location that holds user data.
+pub fn back_up_location_before_changing(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    location: &UserDataLocation,
+) -> Result<(), Error> {
+    if !settings.backup_policy.before_changes {
+        return Ok(());
+    }
+
+    back_up_changed_worlds(settings, app_directories, location)?;
+
+    Ok(())
+}
+
+pub fn backup(
+    action: BackupAction,
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+) -> Result<(), Error> {
+    let backup_store = BackupStore::new(app_directories.backups_path());
+
+    match action {
+        BackupAction::Create { location, world } => {
+            back_up(settings, app_directories, &location, world.as_deref())?;
+        }
+        BackupAction::List { location } => {
+            let location_path = location.map(|location| location.backup_directory_path());
+            let backups = backup_store.list(location_path.as_deref())?;
+            for backup in backups.iter() {
+                print_backup(backup);
+            }
+            println!(
+                "{} backups, {} in total",
+                backups.len(),
+                HumanBytes(backups.iter().map(|backup| backup.size).sum())
+            );
+        }
+        BackupAction::Restore {
+            archive_path,
+            location,
+            force,
+        } => {
+            let save_path = location
+                .resolve(settings, app_directories)?
+                .join(SAVE_DIRECTORY_NAME);
+            let archived_save = inspect(&archive_path)?;
+            let newer_worlds = find_newer_worlds(&archived_save, &save_path)?;
+            if !newer_worlds.is_empty() {
+                let newer_worlds = newer_worlds
+                    .iter()
+                    .map(|world| world.display().to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                if !force {
+                    return Err(anyhow!(
+                        "{} of {} changed after this backup was made, pass --force to overwrite anyway",
+                        newer_worlds,
+                        location
+                    ));
+                }
+                println!("Overwriting newer {} as asked.", newer_worlds);
+            }
+
+            restore(&archive_path, &archived_save, &save_path)?;
+            println!(
+                "Restored {} worlds of {} into {}",
+                archived_save.worlds.len(),
+                archive_path.display(),
+                save_path.display()
+            );
+        }
+        BackupAction::Prune { location, keep } => {
+            let location_path = location.map(|location| location.backup_directory_path());
+            let keep = keep.unwrap_or(settings.backup_policy.keep);
+            if keep == 0 {
+                println!("Retention count is 0, keeping every backup.");
+                return Ok(());
+            }
+            let removed = backup_store.prune(location_path.as_deref(), keep)?;
+            for backup in removed.iter() {
+                print_backup(backup);
+            }
+            println!(
+                "Removed {} backups, freed {}",
+                removed.len(),
+                HumanBytes(removed.iter().map(|backup| backup.size).sum())
+            );
+        }
+    }
+
+    Ok(())
+}
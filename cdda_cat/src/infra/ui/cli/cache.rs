@@ -0,0 +1,103 @@
+use anyhow::Error;
+use cdda_cat_data::entities::{ArchiveCachePolicy, ByteSize, RootDownloadDirectoryPath};
+use chrono::{DateTime, Local};
+use indicatif::HumanBytes;
+
+use crate::infra::fs::cache::{list_cached_archives, remove_archives, select_archives_to_prune};
+
+use super::prompt::confirm;
+
+#[derive(Debug, Clone)]
+pub enum CacheAction {
+    List,
+    Prune {
+        keep_last: Option<usize>,
+        max_total_size: Option<ByteSize>,
+    },
+    Clear {
+        assume_yes: bool,
+    },
+}
+
+pub fn cache(
+    action: CacheAction,
+    root_download_directory_path: &RootDownloadDirectoryPath,
+    policy: &ArchiveCachePolicy,
+) -> Result<(), Error> {
+    let archives = list_cached_archives(root_download_directory_path)?;
+
+    match action {
+        CacheAction::List => {
+            for archive in &archives {
+                println!(
+                    "{}  {:>10}  {}",
+                    DateTime::<Local>::from(archive.modified).format("%Y-%m-%d %H:%M"),
+                    HumanBytes(archive.size).to_string(),
+                    archive.path.display()
+                );
+            }
+            println!(
+                "{} archives, {} in total",
+                archives.len(),
+                HumanBytes(archives.iter().map(|archive| archive.size).sum())
+            );
+        }
+        CacheAction::Prune {
+            keep_last,
+            max_total_size,
+        } => {
+            let policy = ArchiveCachePolicy {
+                keep_last: keep_last.or(policy.keep_last),
+                max_total_size: max_total_size.or(policy.max_total_size),
+                ..policy.clone()
+            };
+            if policy.keep_last.is_none() && policy.max_total_size.is_none() {
+                println!("No retention limits given or configured, nothing to prune.");
+                return Ok(());
+            }
+            prune_archives(root_download_directory_path, &policy)?;
+        }
+        CacheAction::Clear { assume_yes } => {
+            if archives.is_empty() {
+                println!("The archive cache is empty already.");
+                return Ok(());
+            }
+            println!(
+                "Found {} archives, {} in total:",
+                archives.len(),
+                HumanBytes(archives.iter().map(|archive| archive.size).sum())
+            );
+            for archive in &archives {
+                println!("  {}", archive.path.display());
+            }
+            if !assume_yes && !confirm(&format!("Remove these {} archives?", archives.len()))? {
+                println!("Aborted, nothing was removed.");
+                return Ok(());
+            }
+
+            let freed = remove_archives(&archives)?;
+            println!("Cleared archive cache, freed {}", HumanBytes(freed));
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes whatever archives exceed the retention limits of `policy`.
+pub fn prune_archives(
+    root_download_directory_path: &RootDownloadDirectoryPath,
+    policy: &ArchiveCachePolicy,
+) -> Result<(), Error> {
+    let archives = list_cached_archives(root_download_directory_path)?;
+    let to_prune = select_archives_to_prune(&archives, policy);
+    if !to_prune.is_empty() {
+        let freed = remove_archives(&to_prune)?;
+        println!(
+            "Pruned {} archives, freed {}",
+            to_prune.len(),
+            HumanBytes(freed)
+        );
+    }
+
+    Ok(())
+}
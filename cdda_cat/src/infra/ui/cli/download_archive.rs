@@ -29,6 +29,14 @@ pub async fn download_archive(from_url: &str, to_path: &PathBuf) -> Result<(), E
     }
 }
 
+pub fn download_progress_bar(total_length: u64) -> ProgressBar {
+    let progress_bar = ProgressBar::new(total_length);
+    progress_bar.set_style(ProgressStyle::with_template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
+    .unwrap()
+        .progress_chars("#>-"));
+    progress_bar
+}
+
 impl DownloadInfo {
     async fn download_with_progress_bar(
         &mut self,
@@ -45,10 +53,7 @@ impl DownloadInfo {
             .content_length()
             .ok_or(anyhow!("Failed to get content length from '{}'", &self.url))?;
 
-        let progress_bar = ProgressBar::new(total_length);
-        progress_bar.set_style(ProgressStyle::with_template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
-    .unwrap()
-        .progress_chars("#>-"));
+        let progress_bar = download_progress_bar(total_length);
         progress_bar.set_message(progress_bar_info.pre_message.to_string());
         self.download_file(&progress_bar, response).await?;
         progress_bar.set_message(progress_bar_info.post_message.to_string());
@@ -0,0 +1,51 @@
+use anyhow::{anyhow, Error};
+use cdda_cat_data::entities::InstallationId;
+use cdda_cat_lib::installation_manager::AppSettings;
+use std::path::Path;
+
+fn find_installation(
+    settings: &AppSettings,
+    installation_id: &InstallationId,
+) -> Result<(), Error> {
+    if settings
+        .installed_games
+        .iter()
+        .any(|asset| installation_id.matches(asset))
+    {
+        Ok(())
+    } else {
+        Err(anyhow!("{} is not installed!", installation_id))
+    }
+}
+
+pub fn set_favorite(
+    settings: &mut AppSettings,
+    settings_file_path: &Path,
+    installation_id: InstallationId,
+    is_favorite: bool,
+) -> Result<(), Error> {
+    settings
+        .favorite_installations
+        .retain(|favorite| *favorite != installation_id);
+    if is_favorite {
+        find_installation(settings, &installation_id)?;
+        println!("Marked {} as favorite.", installation_id);
+        settings.favorite_installations.push(installation_id);
+    } else {
+        println!("{} is no longer a favorite.", installation_id);
+    }
+
+    settings.write_to_file(settings_file_path)
+}
+
+pub fn set_default(
+    settings: &mut AppSettings,
+    settings_file_path: &Path,
+    installation_id: InstallationId,
+) -> Result<(), Error> {
+    find_installation(settings, &installation_id)?;
+    println!("{} is now launched by default.", installation_id);
+    settings.default_installation = Some(installation_id);
+
+    settings.write_to_file(settings_file_path)
+}
@@ -0,0 +1,573 @@
+use anyhow::{anyhow, Error};
+use cdda_cat_data::entities::{Asset, InstallationId};
+use cdda_cat_lib::installation_manager::AppSettings;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+
+use crate::infra::fs::app_directories::AppDirectories;
+use crate::infra::fs::game_options::{
+    read_external_options, read_option_names, GameOptions, OPTIONS_FILE_PATH,
+};
+
+use super::location::UserDataLocation;
+use super::prompt::confirm;
+use super::world::ensure_game_not_running;
+
+#[derive(Debug, Clone)]
+pub enum GameConfigAction {
+    Get {
+        location: UserDataLocation,
+        name: String,
+    },
+    Set {
+        location: UserDataLocation,
+        name: String,
+        value: String,
+    },
+    List {
+        location: UserDataLocation,
+    },
+    Diff {
+        location: UserDataLocation,
+        against: UserDataLocation,
+    },
+    Changes {
+        from: InstallationId,
+        to: InstallationId,
+        carry_over: bool,
+    },
+}
+
+/// Option names split into words, to tell renamed options apart.
+fn name_words(name: &str) -> BTreeSet<&str> {
+    name.split('_').filter(|word| !word.is_empty()).collect()
+}
+
+/// Pairs removed options with an added one spelled the same but for
+/// underscores, or sharing most of its name's words, as long as that pairing
+/// is unambiguous.
+fn find_renamed_options(removed: &[String], added: &[String]) -> Vec<(String, String)> {
+    let similarity = |from: &str, to: &str| {
+        if from.replace('_', "") == to.replace('_', "") {
+            return 1.0;
+        }
+        let from_words = name_words(from);
+        let to_words = name_words(to);
+        let shared = from_words.intersection(&to_words).count();
+        let total = from_words.union(&to_words).count();
+        if total == 0 {
+            0.0
+        } else {
+            shared as f64 / total as f64
+        }
+    };
+
+    let mut renamed_options = Vec::new();
+    for from in removed {
+        let candidates = added
+            .iter()
+            .filter(|to| similarity(from, to) >= 0.5)
+            .collect::<Vec<&String>>();
+        if let [to] = candidates.as_slice() {
+            let is_unambiguous = removed
+                .iter()
+                .filter(|other| similarity(other, to) >= 0.5)
+                .count()
+                == 1;
+            if is_unambiguous {
+                renamed_options.push((from.to_string(), to.to_string()));
+            }
+        }
+    }
+
+    renamed_options
+}
+
+/// Options an installation itself knows about.
+struct BuildOptions {
+    /// `EXTERNAL_OPTION`s of its data.
+    external_names: BTreeSet<String>,
+    /// Options the game wrote the last time it ran, the only place options
+    /// built into the game show up in. Falls back to a `config/options.json`
+    /// in the installation for builds last launched before that got
+    /// recorded.
+    written_names: Option<BTreeSet<String>>,
+}
+
+impl BuildOptions {
+    fn read(
+        settings: &AppSettings,
+        app_directories: &AppDirectories,
+        asset: &Asset,
+    ) -> Result<Self, Error> {
+        let installation_path = settings.installation_path_of(asset);
+        let written_names = match read_option_names(
+            &app_directories.option_names_file_path(&InstallationId::from(asset)),
+        )? {
+            Some(written_names) => Some(written_names),
+            None => GameOptions::read(&installation_path)?
+                .map(|options| options.values().into_iter().map(|(name, _)| name).collect()),
+        };
+
+        Ok(Self {
+            external_names: read_external_options(&installation_path)?
+                .into_keys()
+                .collect(),
+            written_names,
+        })
+    }
+
+    /// Every option known, leaving out built-in ones unless `with_built_in`.
+    fn names(&self, with_built_in: bool) -> BTreeSet<String> {
+        let mut names = self.external_names.clone();
+        if let Some(written_names) = self.written_names.as_ref().filter(|_| with_built_in) {
+            names.extend(written_names.iter().cloned());
+        }
+        names
+    }
+}
+
+/// The options a location's game reads, next to everything we know the
+/// installations behind it accept.
+struct LocationOptions {
+    user_data_path: PathBuf,
+    /// `None` until the game wrote its `config/options.json` there.
+    options: Option<GameOptions>,
+    /// `EXTERNAL_OPTION`s of the installations' data, with their defaults.
+    external_options: BTreeMap<String, String>,
+    known_names: BTreeSet<String>,
+}
+
+impl LocationOptions {
+    fn read(
+        settings: &AppSettings,
+        app_directories: &AppDirectories,
+        location: &UserDataLocation,
+    ) -> Result<Self, Error> {
+        let installation_paths = location
+            .installations(settings)
+            .into_iter()
+            .map(|asset| settings.installation_path_of(asset))
+            .collect::<Vec<PathBuf>>();
+
+        Self::collect(
+            location.resolve(settings, app_directories)?,
+            &installation_paths,
+        )
+    }
+
+    /// Options of `asset` alone, read from wherever its game keeps them.
+    fn read_installation(
+        settings: &AppSettings,
+        app_directories: &AppDirectories,
+        asset: &Asset,
+    ) -> Result<Self, Error> {
+        Self::collect(
+            UserDataLocation::of_installation(settings, asset)
+                .resolve(settings, app_directories)?,
+            &[settings.installation_path_of(asset)],
+        )
+    }
+
+    fn collect(user_data_path: PathBuf, installation_paths: &[PathBuf]) -> Result<Self, Error> {
+        let options = GameOptions::read(&user_data_path)?;
+
+        let mut external_options = BTreeMap::new();
+        let mut known_names = BTreeSet::new();
+        for installation_path in installation_paths {
+            external_options.extend(read_external_options(installation_path)?);
+            // Installations that ran without a user directory once wrote
+            // their own options.
+            if *installation_path != user_data_path {
+                if let Some(installation_options) = GameOptions::read(installation_path)? {
+                    known_names.extend(
+                        installation_options
+                            .values()
+                            .into_iter()
+                            .map(|(name, _)| name),
+                    );
+                }
+            }
+        }
+        known_names.extend(external_options.keys().cloned());
+        if let Some(options) = &options {
+            known_names.extend(options.values().into_iter().map(|(name, _)| name));
+        }
+
+        Ok(Self {
+            user_data_path,
+            options,
+            external_options,
+            known_names,
+        })
+    }
+
+    /// The value the game uses, falling back to the data's default for
+    /// options it didn't write yet.
+    fn value_of(&self, name: &str) -> Option<String> {
+        self.options
+            .as_ref()
+            .and_then(|options| options.get(name))
+            .or_else(|| self.external_options.get(name).cloned())
+    }
+
+    fn ensure_known(&self, location: &UserDataLocation, name: &str) -> Result<(), Error> {
+        if self.known_names.contains(name) {
+            return Ok(());
+        }
+
+        match self.options {
+            Some(_) => Err(anyhow!(
+                "{} has no option {}, see game-config list for the ones it has",
+                location,
+                name
+            )),
+            None => Err(anyhow!(
+                "{} has no option {} and no {} to check it against, launch the game with it once first",
+                location,
+                name,
+                OPTIONS_FILE_PATH
+            )),
+        }
+    }
+}
+
+pub fn game_config(
+    action: GameConfigAction,
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+) -> Result<(), Error> {
+    match action {
+        GameConfigAction::Get { location, name } => {
+            let location_options = LocationOptions::read(settings, app_directories, &location)?;
+            location_options.ensure_known(&location, &name)?;
+            match location_options.value_of(&name) {
+                Some(value) => println!("{}", value),
+                None => return Err(anyhow!("{} doesn't set {} yet", location, name)),
+            }
+        }
+        GameConfigAction::Set {
+            location,
+            name,
+            value,
+        } => {
+            // The game writes its options back when it quits.
+            ensure_game_not_running(settings, app_directories, &location)?;
+            let location_options = LocationOptions::read(settings, app_directories, &location)?;
+            location_options.ensure_known(&location, &name)?;
+
+            let mut options = location_options.options.clone().unwrap_or_default();
+            let previous_value = options.set(&name, &value);
+            options.write(&location_options.user_data_path)?;
+            match previous_value {
+                Some(previous_value) => println!(
+                    "Set {} to {} in {}, was {}",
+                    name, value, location, previous_value
+                ),
+                None => println!("Set {} to {} in {}", name, value, location),
+            }
+        }
+        GameConfigAction::List { location } => {
+            let location_options = LocationOptions::read(settings, app_directories, &location)?;
+            let values = location_options
+                .options
+                .as_ref()
+                .map(GameOptions::values)
+                .unwrap_or_default();
+            for (name, value) in values.iter() {
+                println!("{} = {}", name, value);
+            }
+            let mut unset_count = 0;
+            for (name, default_value) in location_options.external_options.iter() {
+                if values.iter().all(|(set_name, _)| set_name != name) {
+                    println!("{} = {} (default)", name, default_value);
+                    unset_count += 1;
+                }
+            }
+
+            if location_options.options.is_none() && unset_count == 0 {
+                println!(
+                    "{} has no {} yet, launch the game with it once first",
+                    location, OPTIONS_FILE_PATH
+                );
+            } else {
+                println!("{} options in {}", values.len() + unset_count, location);
+            }
+        }
+        GameConfigAction::Diff { location, against } => {
+            let location_options = LocationOptions::read(settings, app_directories, &location)?;
+            let against_options = LocationOptions::read(settings, app_directories, &against)?;
+            let names = location_options
+                .known_names
+                .union(&against_options.known_names)
+                .cloned()
+                .collect::<BTreeSet<String>>();
+
+            let mut difference_count = 0;
+            for name in names.iter() {
+                let value = location_options.value_of(name);
+                let against_value = against_options.value_of(name);
+                if value == against_value {
+                    continue;
+                }
+
+                difference_count += 1;
+                match (value, against_value) {
+                    (Some(value), Some(against_value)) => {
+                        println!("{}: {} -> {}", name, value, against_value)
+                    }
+                    (Some(value), None) => println!("{}: {} -> unset", name, value),
+                    (None, Some(against_value)) => {
+                        println!("{}: unset -> {}", name, against_value)
+                    }
+                    (None, None) => {}
+                }
+            }
+
+            if difference_count == 0 {
+                println!("{} and {} use the same options", location, against);
+            } else {
+                println!(
+                    "{} options differ between {} and {}",
+                    difference_count, location, against
+                );
+            }
+        }
+        GameConfigAction::Changes {
+            from,
+            to,
+            carry_over,
+        } => {
+            let from_asset = settings
+                .find_installation(&from)
+                .ok_or_else(|| anyhow!("{} is not installed!", from))?;
+            let to_asset = settings
+                .find_installation(&to)
+                .ok_or_else(|| anyhow!("{} is not installed!", to))?;
+            let from_build_options = BuildOptions::read(settings, app_directories, from_asset)?;
+            let to_build_options = BuildOptions::read(settings, app_directories, to_asset)?;
+            let from_options =
+                LocationOptions::read_installation(settings, app_directories, from_asset)?;
+            let to_options =
+                LocationOptions::read_installation(settings, app_directories, to_asset)?;
+
+            // Built-in options only compare when both builds wrote theirs.
+            let with_built_in = from_build_options.written_names.is_some()
+                && to_build_options.written_names.is_some();
+            let from_names = from_build_options.names(with_built_in);
+            let to_names = to_build_options.names(with_built_in);
+            let added = to_names
+                .difference(&from_names)
+                .cloned()
+                .collect::<Vec<String>>();
+            let removed = from_names
+                .difference(&to_names)
+                .cloned()
+                .collect::<Vec<String>>();
+            let renamed_options = find_renamed_options(&removed, &added);
+            let is_renamed = |name: &String| {
+                renamed_options
+                    .iter()
+                    .any(|(renamed_from, renamed_to)| renamed_from == name || renamed_to == name)
+            };
+
+            if !renamed_options.is_empty() {
+                println!("Probably renamed in {}:", to);
+                for (renamed_from, renamed_to) in renamed_options.iter() {
+                    println!("  {} -> {}", renamed_from, renamed_to);
+                }
+            }
+            if added.iter().any(|name| !is_renamed(name)) {
+                println!("New in {}:", to);
+                for name in added.iter().filter(|name| !is_renamed(name)) {
+                    match to_options.value_of(name) {
+                        Some(value) => println!("  {} = {}", name, value),
+                        None => println!("  {}", name),
+                    }
+                }
+            }
+            if removed.iter().any(|name| !is_renamed(name)) {
+                println!("Gone from {}:", to);
+                for name in removed.iter().filter(|name| !is_renamed(name)) {
+                    println!("  {}", name);
+                }
+            }
+            if added.is_empty() && removed.is_empty() {
+                println!("{} and {} know the same options", from, to);
+            }
+            if !with_built_in {
+                println!(
+                    "Only compared the options of their data, launch both once to compare the options built into the game too"
+                );
+            }
+
+            // Builds sharing their user data only have renamed options to
+            // carry over.
+            let carried_values = from_options
+                .options
+                .as_ref()
+                .map(GameOptions::values)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|(name, value)| {
+                    let to_name = renamed_options
+                        .iter()
+                        .find(|(renamed_from, _)| *renamed_from == name)
+                        .map(|(_, renamed_to)| renamed_to.to_string())
+                        .unwrap_or(name);
+                    let is_carried = (to_names.contains(&to_name)
+                        || to_options.known_names.contains(&to_name))
+                        && to_options.value_of(&to_name).as_ref() != Some(&value);
+                    is_carried.then_some((to_name, value))
+                })
+                .collect::<Vec<(String, String)>>();
+            if carried_values.is_empty() {
+                println!("{} already uses the values of {}", to, from);
+                return Ok(());
+            }
+
+            let to_location = UserDataLocation::of_installation(settings, to_asset);
+            println!("Values of {} that {} doesn't use yet:", from, to_location);
+            for (name, value) in carried_values.iter() {
+                match to_options.value_of(name) {
+                    Some(to_value) => println!("  {}: {} -> {}", name, to_value, value),
+                    None => println!("  {}: unset -> {}", name, value),
+                }
+            }
+
+            let is_carrying_over = carry_over
+                || (io::stdin().is_terminal()
+                    && confirm(&format!(
+                        "Carry {} values over to {}?",
+                        carried_values.len(),
+                        to_location
+                    ))?);
+            if !is_carrying_over {
+                println!("Pass --carry_over to copy them");
+                return Ok(());
+            }
+
+            ensure_game_not_running(settings, app_directories, &to_location)?;
+            let mut options = to_options.options.clone().unwrap_or_default();
+            for (name, value) in carried_values.iter() {
+                options.set(name, value);
+            }
+            options.write(&to_options.user_data_path)?;
+            println!(
+                "Carried {} values over to {}",
+                carried_values.len(),
+                to_location
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::test_directory::TestDirectory;
+    use std::fs;
+    use std::path::Path;
+
+    fn write_file(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn pairs_options_renamed_by_underscores_or_most_words() {
+        assert_eq!(
+            find_renamed_options(
+                &names(&["AUTO_SAVE", "SHOW_ITEM_SYMBOLS", "SIDEBAR_STYLE"]),
+                &names(&["AUTOSAVE", "ITEM_SYMBOLS", "MESSAGE_LOG_STYLE"]),
+            ),
+            vec![
+                ("AUTO_SAVE".to_string(), "AUTOSAVE".to_string()),
+                ("SHOW_ITEM_SYMBOLS".to_string(), "ITEM_SYMBOLS".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn needs_half_of_the_words_in_common() {
+        // One word of three.
+        assert!(find_renamed_options(&names(&["FONT_WIDTH"]), &names(&["FONT_HEIGHT"])).is_empty());
+        // Two words of four, just enough.
+        assert_eq!(
+            find_renamed_options(
+                &names(&["OVERMAP_FONT_WIDTH"]),
+                &names(&["OVERMAP_FONT_BLENDING"])
+            ),
+            vec![(
+                "OVERMAP_FONT_WIDTH".to_string(),
+                "OVERMAP_FONT_BLENDING".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn leaves_ambiguous_renames_alone() {
+        // Two added options match the removed one.
+        assert!(find_renamed_options(
+            &names(&["TERMINAL_X"]),
+            &names(&["TERMINAL_X_SIZE", "TERMINAL_X_OFFSET"])
+        )
+        .is_empty());
+        // Two removed options match the added one.
+        assert!(find_renamed_options(
+            &names(&["TERMINAL_X", "TERMINAL_X_LEGACY"]),
+            &names(&["TERMINAL_X_SIZE"])
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn knows_only_options_some_file_mentions() {
+        let test_directory = TestDirectory::create();
+        let user_data_path = test_directory.join("userdir");
+        let installation_path = test_directory.join("installation");
+        let location = UserDataLocation::UserDirectory("main".to_string());
+        write_file(
+            &installation_path.join("data/core/external_options.json"),
+            r#"[{ "type": "EXTERNAL_OPTION", "name": "SPAWN_DENSITY", "value": "1.0" }]"#,
+        );
+
+        let location_options = LocationOptions::collect(
+            user_data_path.clone(),
+            std::slice::from_ref(&installation_path),
+        )
+        .unwrap();
+        location_options
+            .ensure_known(&location, "SPAWN_DENSITY")
+            .unwrap();
+        let error = location_options
+            .ensure_known(&location, "AUTOSAVE")
+            .unwrap_err();
+        assert!(error.to_string().contains("launch the game with it once"));
+
+        write_file(
+            &GameOptions::options_file_path(&user_data_path),
+            r#"[{ "name": "AUTOSAVE", "value": "false" }]"#,
+        );
+        write_file(
+            &GameOptions::options_file_path(&installation_path),
+            r#"[{ "name": "AUTO_PICKUP", "value": "false" }]"#,
+        );
+        let location_options =
+            LocationOptions::collect(user_data_path, &[installation_path]).unwrap();
+        for name in ["SPAWN_DENSITY", "AUTOSAVE", "AUTO_PICKUP"] {
+            location_options.ensure_known(&location, name).unwrap();
+        }
+        let error = location_options
+            .ensure_known(&location, "AUTOSAVES")
+            .unwrap_err();
+        assert!(error.to_string().contains("see game-config list"));
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::Error;
+use cdda_cat_lib::installation_manager::AppSettings;
+use std::path::{Path, PathBuf};
+
+use crate::infra::fs::app_directories::AppDirectories;
+use crate::infra::fs::gc::{find_garbage, remove_garbage, Garbage};
+use crate::infra::fs::user_data::{archive_user_data, find_user_data};
+
+use super::plan::Plan;
+use super::prompt::confirm;
+use super::uninstall::user_data_archive_path;
+
+/// Names the user data of a leftover game directory after the tag and
+/// edition directories it sits in, plus its own name to tell an orphaned
+/// installation and its `.CDDA.previous` apart.
+fn user_data_archive_name(path: &Path) -> String {
+    let names = path
+        .components()
+        .rev()
+        .take(3)
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<String>>();
+    match names.as_slice() {
+        [directory_name, edition, tag] => format!(
+            "{}-{}-{}",
+            tag,
+            edition,
+            directory_name.trim_start_matches('.')
+        ),
+        _ => path.display().to_string().replace('/', "-"),
+    }
+}
+
+/// A leftover with where its saves and configuration get archived to before
+/// it is removed, if it has any.
+struct GarbageToRemove {
+    item: Garbage,
+    preserved_user_data: Option<(PathBuf, Vec<PathBuf>)>,
+}
+
+/// Leftovers below the root download directory.
+fn find_garbage_to_remove(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+) -> Result<Vec<GarbageToRemove>, Error> {
+    let garbage = find_garbage(
+        &app_directories.root_download_directory_path(settings)?,
+        &settings.decompressed_game_directory_path,
+        &settings.installed_games,
+    )?;
+
+    garbage
+        .into_iter()
+        .map(|item| {
+            let user_data_paths = if item.path.is_dir() && !item.path.is_symlink() {
+                find_user_data(&item.path)?
+            } else {
+                Vec::new()
+            };
+            let preserved_user_data = (!user_data_paths.is_empty()).then(|| {
+                (
+                    user_data_archive_path(app_directories, &user_data_archive_name(&item.path)),
+                    user_data_paths,
+                )
+            });
+            Ok(GarbageToRemove {
+                item,
+                preserved_user_data,
+            })
+        })
+        .collect()
+}
+
+pub fn plan_gc(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    use_trash: bool,
+) -> Result<Plan, Error> {
+    let mut plan = Plan::default();
+    for GarbageToRemove {
+        item,
+        preserved_user_data,
+    } in find_garbage_to_remove(settings, app_directories)?
+    {
+        if let Some((archive_path, _)) = preserved_user_data {
+            plan.preserved_user_data.push(archive_path);
+        }
+        if use_trash {
+            plan.trashed_paths.push(item.path);
+        } else if item.path.is_dir() {
+            plan.removed_directories.push(item.path);
+        } else {
+            plan.removed_files.push(item.path);
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Lists the leftovers and removes them once confirmed, archiving the saves
+/// and configuration of leftover installations first.
+pub fn gc(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    use_trash: bool,
+    assume_yes: bool,
+) -> Result<(), Error> {
+    let garbage = find_garbage_to_remove(settings, app_directories)?;
+    if garbage.is_empty() {
+        println!("Nothing to clean up.");
+        return Ok(());
+    }
+
+    println!("Found {} leftovers:", garbage.len());
+    for GarbageToRemove {
+        item,
+        preserved_user_data,
+    } in garbage.iter()
+    {
+        println!("  {} ({})", item.path.display(), item.kind);
+        if let Some((archive_path, _)) = preserved_user_data {
+            println!(
+                "    its saves and configuration get archived to {}",
+                archive_path.display()
+            );
+        }
+    }
+    if !assume_yes && !confirm(&format!("Remove these {} leftovers?", garbage.len()))? {
+        println!("Aborted, nothing was removed.");
+        return Ok(());
+    }
+
+    for GarbageToRemove {
+        item,
+        preserved_user_data,
+    } in garbage.iter()
+    {
+        if let Some((archive_path, user_data_paths)) = preserved_user_data {
+            archive_user_data(&item.path, user_data_paths, archive_path)?;
+            println!(
+                "Saved the user data of {} to {}",
+                item.path.display(),
+                archive_path.display()
+            );
+        }
+    }
+    let garbage = garbage
+        .into_iter()
+        .map(|garbage_to_remove| garbage_to_remove.item)
+        .collect::<Vec<Garbage>>();
+    remove_garbage(&garbage, use_trash)?;
+    println!("Cleaned up {} leftovers.", garbage.len());
+
+    Ok(())
+}
@@ -0,0 +1,195 @@
+use anyhow::{anyhow, Error};
+use cdda_cat_data::entities::{ArchiveFilePath, Asset, Edition, InstallationId};
+use cdda_cat_lib::installation_manager::{AppSettings, CDDARelease};
+use std::fs::{self, create_dir_all};
+
+use crate::infra::archive::unpacker::{ArchiveAsset, ArchiveUnpacker};
+use crate::infra::fs::app_directories::AppDirectories;
+use crate::infra::interrupt;
+
+use super::backup::back_up_before_changing;
+use super::cache::prune_archives;
+use super::download_archive::download_archive;
+use super::plan::{Plan, PlannedDownload};
+use super::prune::plan_prune_after_install;
+use super::stream_archive::stream_archive;
+
+#[derive(Debug, Clone, Default)]
+pub struct InstallOptions {
+    pub download_only: bool,
+    pub overwrite: bool,
+    pub stream: bool,
+    pub keep_archive: bool,
+}
+
+/// The asset to install and where its archive gets downloaded to.
+fn prepare_asset(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    release: &CDDARelease,
+    edition: &Edition,
+) -> Result<(Asset, ArchiveFilePath), Error> {
+    let root_download_directory_path = app_directories.root_download_directory_path(settings)?;
+    let mut asset = release
+        .linux_asset(edition)
+        .ok_or_else(|| anyhow!("Cannot find asset! Maybe try again with different edition?"))?;
+
+    let game_edition_directory_path =
+        root_download_directory_path.to_game_edition_directory_path(&asset);
+    let archive_path = ArchiveFilePath::new(game_edition_directory_path.join(&asset.name));
+    asset.game_edition_directory_path = game_edition_directory_path;
+
+    Ok((asset, archive_path))
+}
+
+/// Why installing `asset` has to stop before changing anything, which is
+/// when it would overwrite an installation unasked.
+fn refusal_reason(settings: &AppSettings, asset: &Asset, overwrite: bool) -> Option<String> {
+    let destination_path = asset
+        .game_edition_directory_path
+        .join(settings.decompressed_game_directory_path.as_path());
+    (destination_path.exists() && !overwrite).then(|| {
+        format!(
+            "{} already exists and we won't overwrite it! Retry with --overwrite flag to force installation directory overwrite.",
+            destination_path.display()
+        )
+    })
+}
+
+/// What `install` would do with the same arguments.
+pub fn plan_install(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    release: &CDDARelease,
+    edition: &Edition,
+    options: &InstallOptions,
+) -> Result<Plan, Error> {
+    let (asset, archive_path) = prepare_asset(settings, app_directories, release, edition)?;
+    let game_edition_directory_path = &asset.game_edition_directory_path;
+    let mut plan = Plan::default();
+    if let Some(refusal_reason) = refusal_reason(settings, &asset, options.overwrite) {
+        plan.refusals.push(refusal_reason);
+        return Ok(plan);
+    }
+
+    if !game_edition_directory_path.exists() {
+        plan.created_directories
+            .push(game_edition_directory_path.to_path_buf());
+    }
+    if !archive_path.exists() {
+        plan.downloads.push(PlannedDownload {
+            url: asset.url.to_string(),
+            path: archive_path.to_path_buf(),
+            bytes: release
+                .assets
+                .iter()
+                .find(|raw_asset| raw_asset.name == asset.name)
+                .map(|raw_asset| raw_asset.size)
+                .unwrap_or_default(),
+        });
+    }
+    if options.download_only {
+        return Ok(plan);
+    }
+
+    let destination_path =
+        game_edition_directory_path.join(settings.decompressed_game_directory_path.as_path());
+    if destination_path.exists() {
+        plan.replaced_directories.push(destination_path);
+    } else {
+        plan.created_directories.push(destination_path);
+    }
+    if !settings.installed_games.contains(&asset) {
+        plan.registered_installations
+            .push(InstallationId::from(&asset));
+    }
+    if settings.archive_cache_policy.delete_after_install {
+        plan.removed_files.push(archive_path.to_path_buf());
+    }
+
+    let mut installed_settings = settings.clone();
+    if !installed_settings.installed_games.contains(&asset) {
+        installed_settings.installed_games.push(asset.clone());
+    }
+    plan.extend(plan_prune_after_install(
+        &installed_settings,
+        app_directories,
+        Some(&asset),
+    )?);
+
+    Ok(plan)
+}
+
+/// Downloads and unpacks the Linux build of `edition` from `release`,
+/// returning the asset as registered in `settings`.
+pub async fn install(
+    settings: &mut AppSettings,
+    app_directories: &AppDirectories,
+    release: &CDDARelease,
+    edition: &Edition,
+    options: &InstallOptions,
+) -> Result<Asset, Error> {
+    let root_download_directory_path = app_directories.root_download_directory_path(settings)?;
+    let (asset, archive_path) = prepare_asset(settings, app_directories, release, edition)?;
+    if let Some(refusal_reason) = refusal_reason(settings, &asset, options.overwrite) {
+        return Err(anyhow!(refusal_reason));
+    }
+    let game_edition_directory_path = asset.game_edition_directory_path.clone();
+    if !options.download_only {
+        back_up_before_changing(settings, app_directories, &InstallationId::from(&asset))?;
+    }
+    create_dir_all(game_edition_directory_path.as_path())?;
+
+    let deduplicating_content_store = settings
+        .deduplicate_installations
+        .then(|| app_directories.content_store());
+    if options.stream && !options.download_only && !archive_path.exists() {
+        let _ctrl_c_guard = interrupt::watch_for_ctrl_c();
+        let (compressed, download_task) =
+            stream_archive(&asset.url, &archive_path, options.keep_archive).await?;
+        // The decompressors block on the download, so they get a thread of
+        // their own and leave the runtime free to keep downloading.
+        let mut unpacked_settings = settings.clone();
+        let mut archive_asset =
+            ArchiveAsset::new(asset.clone()).with_content_store(deduplicating_content_store);
+        let unpacked_game_edition_directory_path = game_edition_directory_path.clone();
+        let settings_file_path = app_directories.settings_file_path.clone();
+        let unpack_task = tokio::task::spawn_blocking(move || {
+            archive_asset
+                .unpack_stream(
+                    &mut unpacked_settings,
+                    compressed,
+                    &unpacked_game_edition_directory_path,
+                    &settings_file_path,
+                )
+                .map(|()| unpacked_settings)
+        });
+        let unpack_result = unpack_task.await?;
+        let download_result = download_task.await?;
+        *settings = unpack_result?;
+        download_result?;
+    } else {
+        download_archive(&asset.url, &archive_path).await?;
+
+        if !options.download_only {
+            let _ctrl_c_guard = interrupt::watch_for_ctrl_c();
+            ArchiveAsset::new(asset.clone())
+                .with_content_store(deduplicating_content_store)
+                .unpack(
+                    settings,
+                    &archive_path,
+                    &game_edition_directory_path,
+                    &app_directories.settings_file_path,
+                )?;
+        }
+    }
+
+    let cache_policy = &settings.archive_cache_policy;
+    if !options.download_only && cache_policy.delete_after_install && archive_path.exists() {
+        fs::remove_file(archive_path.as_path())?;
+        println!("Removed installed archive {}", archive_path.display());
+    }
+    prune_archives(&root_download_directory_path, cache_policy)?;
+
+    Ok(asset)
+}
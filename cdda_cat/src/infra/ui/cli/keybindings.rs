@@ -0,0 +1,200 @@
+use anyhow::{anyhow, Error};
+use cdda_cat_lib::installation_manager::AppSettings;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use crate::infra::fs::app_directories::AppDirectories;
+use crate::infra::fs::keybindings::{
+    read_default_actions, ActionId, Keybindings, MergeReport, KEYBINDINGS_FILE_PATH,
+};
+
+use super::location::UserDataLocation;
+use super::world::ensure_game_not_running;
+
+#[derive(Debug, Clone)]
+pub enum KeybindingsAction {
+    Export {
+        location: UserDataLocation,
+        file_path: PathBuf,
+    },
+    Import {
+        file_path: PathBuf,
+        location: UserDataLocation,
+    },
+    Sync {
+        from: UserDataLocation,
+    },
+}
+
+fn read_custom_keybindings(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    location: &UserDataLocation,
+) -> Result<Keybindings, Error> {
+    Keybindings::read(&location.resolve(settings, app_directories)?)?.ok_or_else(|| {
+        anyhow!(
+            "{} has no custom keybindings in {}",
+            location,
+            KEYBINDINGS_FILE_PATH
+        )
+    })
+}
+
+/// Actions the installations behind `location` ship bindings for, `None`
+/// when none of them has readable defaults.
+fn known_actions(
+    settings: &AppSettings,
+    location: &UserDataLocation,
+) -> Result<Option<BTreeSet<ActionId>>, Error> {
+    let mut known_actions: Option<BTreeSet<ActionId>> = None;
+    for asset in location.installations(settings) {
+        if let Some(actions) = read_default_actions(&settings.installation_path_of(asset))? {
+            known_actions
+                .get_or_insert_with(BTreeSet::new)
+                .extend(actions);
+        }
+    }
+
+    Ok(known_actions)
+}
+
+/// Merges `keybindings` into those of `location` and reports what changed,
+/// which keys now clash and which actions its game doesn't know.
+fn merge_into(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    keybindings: &Keybindings,
+    location: &UserDataLocation,
+) -> Result<MergeReport, Error> {
+    ensure_game_not_running(settings, app_directories, location)?;
+    let user_data_path = location.resolve(settings, app_directories)?;
+    let mut target_keybindings = Keybindings::read(&user_data_path)?.unwrap_or_default();
+    let report = target_keybindings.merge(keybindings);
+    if report.is_unchanged() {
+        println!(
+            "{} already has all {} keybindings",
+            location, report.unchanged
+        );
+        return Ok(report);
+    }
+    target_keybindings.write(&user_data_path)?;
+
+    println!(
+        "Merged keybindings into {}: {} added, {} replaced, {} unchanged",
+        location,
+        report.added.len(),
+        report.replaced.len(),
+        report.unchanged
+    );
+    for action in report.replaced.iter() {
+        println!("  Replaced the binding of {}", action);
+    }
+
+    let merged_actions = report
+        .added
+        .iter()
+        .chain(report.replaced.iter())
+        .collect::<Vec<&ActionId>>();
+    for clash in target_keybindings.find_key_clashes() {
+        let is_merged = clash.actions.iter().any(|id| {
+            merged_actions
+                .iter()
+                .any(|action| action.category == clash.category && action.id == *id)
+        });
+        if is_merged {
+            println!(
+                "  Conflict: {} is bound to {} in {}",
+                clash.key,
+                clash.actions.join(", "),
+                clash.category
+            );
+        }
+    }
+    if let Some(known_actions) = known_actions(settings, location)? {
+        for action in merged_actions {
+            if !known_actions.contains(action) {
+                println!("  Unknown to this build: {}", action);
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+pub fn keybindings(
+    action: KeybindingsAction,
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+) -> Result<(), Error> {
+    match action {
+        KeybindingsAction::Export {
+            location,
+            file_path,
+        } => {
+            let keybindings = read_custom_keybindings(settings, app_directories, &location)?;
+            keybindings.write_file(&file_path)?;
+            println!(
+                "Exported {} keybindings of {} to {}",
+                keybindings.len(),
+                location,
+                file_path.display()
+            );
+        }
+        KeybindingsAction::Import {
+            file_path,
+            location,
+        } => {
+            let keybindings = Keybindings::read_file(&file_path)?;
+            if keybindings.is_empty() {
+                return Err(anyhow!("{} has no keybindings", file_path.display()));
+            }
+            merge_into(settings, app_directories, &keybindings, &location)?;
+        }
+        KeybindingsAction::Sync { from } => {
+            let keybindings = read_custom_keybindings(settings, app_directories, &from)?;
+            let source_path = from.resolve(settings, app_directories)?;
+
+            // Installations sharing a user directory share their bindings.
+            let mut target_paths = vec![source_path];
+            // Like user directories the game hasn't created yet.
+            let mut unresolved_locations = Vec::new();
+            let mut failed_count = 0;
+            for asset in settings.installed_games.iter() {
+                let location = UserDataLocation::of_installation(settings, asset);
+                let user_data_path = match location.resolve(settings, app_directories) {
+                    Ok(user_data_path) => user_data_path,
+                    Err(error) => {
+                        if !unresolved_locations.contains(&location) {
+                            eprintln!("Failed to sync {}: {}", location, error);
+                            unresolved_locations.push(location);
+                        }
+                        continue;
+                    }
+                };
+                if target_paths.contains(&user_data_path) {
+                    continue;
+                }
+                target_paths.push(user_data_path);
+
+                if let Err(error) = merge_into(settings, app_directories, &keybindings, &location) {
+                    eprintln!("Failed to sync {}: {}", location, error);
+                    failed_count += 1;
+                }
+            }
+
+            let target_count = target_paths.len() - 1 + unresolved_locations.len();
+            failed_count += unresolved_locations.len();
+            if target_count == 0 {
+                println!("There are no other installations to sync to");
+            } else if failed_count > 0 {
+                return Err(anyhow!(
+                    "Failed to sync keybindings to {} of {} locations",
+                    failed_count,
+                    target_count
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,288 @@
+use anyhow::{anyhow, Context, Error};
+use cdda_cat_data::entities::{Asset, DowngradeProtection, InstallationId, WorldUsage};
+use cdda_cat_lib::installation_manager::AppSettings;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, ExitStatus};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::infra::fs::app_directories::AppDirectories;
+use crate::infra::fs::backup::{list_worlds, newest_modification_time, SAVE_DIRECTORY_NAME};
+use crate::infra::fs::game_options::{write_option_names, GameOptions};
+use crate::infra::fs::user_data::{copy_recursively, list_user_data_entries, CopyReport};
+use crate::infra::fs::worlds::read_save_version;
+
+use super::backup::{back_up, back_up_changed_worlds};
+use super::location::UserDataLocation;
+use super::prompt::confirm_or_pass;
+
+/// Creates the user directory of `profile` if needed and copies user data
+/// inside `installation_path` it doesn't have yet into it, since the game
+/// won't see that data while using the user directory. Asks first unless the
+/// user directory is new or `copy_user_data` is set.
+fn prepare_user_directory(
+    app_directories: &AppDirectories,
+    profile: &str,
+    installation_path: &Path,
+    copy_user_data: bool,
+) -> Result<PathBuf, Error> {
+    let user_directory_path = app_directories.user_directory_path(profile)?;
+    let is_new = !user_directory_path.exists();
+    let missing_entries = list_user_data_entries(installation_path)?
+        .into_iter()
+        .filter(|entry| fs::symlink_metadata(user_directory_path.join(entry)).is_err())
+        .collect::<Vec<PathBuf>>();
+    if missing_entries.is_empty() {
+        return Ok(user_directory_path);
+    }
+
+    if !is_new {
+        println!(
+            "{} has user data the {} user directory doesn't:",
+            installation_path.display(),
+            profile
+        );
+        for entry in missing_entries.iter() {
+            println!("  {}", entry.display());
+        }
+        println!("The game won't see it there, launch with --no_userdir to use it in place.");
+        let is_copying = copy_user_data
+            || confirm_or_pass(
+                &format!("Copy it into the {} user directory?", profile),
+                "--copy_user_data",
+            )?;
+        if !is_copying {
+            return Err(anyhow!(
+                "Launch cancelled, the game wouldn't see that user data in the {} user directory",
+                profile
+            ));
+        }
+    }
+
+    let mut report = CopyReport::default();
+    for entry in missing_entries.iter() {
+        copy_recursively(
+            &installation_path.join(entry),
+            &user_directory_path.join(entry),
+            &mut report,
+        )?;
+    }
+    println!(
+        "Copied {} files of user data from {} into the {} user directory",
+        report.copied_files,
+        installation_path.display(),
+        profile
+    );
+
+    Ok(user_directory_path)
+}
+
+/// Worlds in `user_data_path` last written by a newer build than `asset`,
+/// or by one it can't be ordered against, with what we know about that
+/// build.
+fn find_downgraded_worlds(
+    settings: &AppSettings,
+    asset: &Asset,
+    user_data_path: &Path,
+) -> Result<Vec<(String, String)>, Error> {
+    let save_path = user_data_path.join(SAVE_DIRECTORY_NAME);
+    let mut downgraded_worlds = Vec::new();
+    for world in list_worlds(&save_path)? {
+        let usage = settings.world_usage_of(user_data_path, &world);
+        let save_version = read_save_version(&save_path.join(&world))?;
+        let last_saved_by = match (
+            settings.is_downgrade(&asset.tag, usage, save_version),
+            usage,
+        ) {
+            (Some(true), Some(usage)) => format!("last saved by {}", usage.installation),
+            (Some(true), None) => "saved in a newer format".to_string(),
+            (None, Some(usage)) => {
+                format!("last saved by {}, which may be newer", usage.installation)
+            }
+            _ => continue,
+        };
+        downgraded_worlds.push((world, last_saved_by));
+    }
+
+    Ok(downgraded_worlds)
+}
+
+/// Warns about worlds `asset` would load with an older build than the one
+/// that saved them, then asks or backs them up as the settings say.
+fn protect_from_downgrade(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    asset: &Asset,
+    location: &UserDataLocation,
+    user_data_path: &Path,
+) -> Result<(), Error> {
+    if settings.downgrade_protection == DowngradeProtection::Off {
+        return Ok(());
+    }
+    let downgraded_worlds = find_downgraded_worlds(settings, asset, user_data_path)?;
+    if downgraded_worlds.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!(
+        "{} may be older than the build that last saved these worlds of {}:",
+        InstallationId::from(asset),
+        location
+    );
+    for (world, last_saved_by) in downgraded_worlds.iter() {
+        eprintln!("  {} ({})", world, last_saved_by);
+    }
+
+    match settings.downgrade_protection {
+        DowngradeProtection::Confirm => {
+            if !confirm_or_pass(
+                "Loading them may corrupt them, launch anyway?",
+                "--allow_downgrade",
+            )? {
+                return Err(anyhow!("Launch cancelled"));
+            }
+        }
+        DowngradeProtection::Backup => {
+            for (world, _) in downgraded_worlds.iter() {
+                back_up(settings, app_directories, location, Some(world))?;
+            }
+        }
+        DowngradeProtection::Off => {}
+    }
+
+    Ok(())
+}
+
+/// Remembers `asset` as the build that last wrote the worlds changed since
+/// `started_at`.
+fn record_world_usages(
+    settings: &mut AppSettings,
+    app_directories: &AppDirectories,
+    asset: &Asset,
+    user_data_path: &Path,
+    started_at: u64,
+) -> Result<(), Error> {
+    let save_path = user_data_path.join(SAVE_DIRECTORY_NAME);
+    let mut is_changed = false;
+    for world in list_worlds(&save_path)? {
+        let world_path = save_path.join(&world);
+        if newest_modification_time(&world_path)? < started_at {
+            continue;
+        }
+
+        settings.record_world_usage(WorldUsage {
+            user_data_path: user_data_path.to_path_buf(),
+            save_version: read_save_version(&world_path)?,
+            world,
+            installation: InstallationId::from(asset),
+        });
+        is_changed = true;
+    }
+
+    if is_changed {
+        settings.write_to_file(&app_directories.settings_file_path)?;
+    }
+
+    Ok(())
+}
+
+/// Remembers the options `asset` knows if the game wrote its options since
+/// `started_at`. The game writes every option it has and no others.
+fn record_option_names(
+    app_directories: &AppDirectories,
+    asset: &Asset,
+    user_data_path: &Path,
+    started_at: u64,
+) -> Result<(), Error> {
+    let options_path = GameOptions::options_file_path(user_data_path);
+    if !options_path.is_file() || newest_modification_time(&options_path)? < started_at {
+        return Ok(());
+    }
+
+    match GameOptions::read(user_data_path)? {
+        Some(options) => write_option_names(
+            &app_directories.option_names_file_path(&InstallationId::from(asset)),
+            &options,
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Runs the launcher of `asset` and waits for the game to quit. The game
+/// gets the user directory of its profile unless `use_user_directory` is
+/// off or the settings disable it, with user data left inside the
+/// installation copied over when `copy_user_data` is set or confirmed. Worlds
+/// it is about to use are checked for downgrades unless `allow_downgrade` is
+/// set, and backed up first when the settings ask for that.
+pub fn launch_installation(
+    settings: &mut AppSettings,
+    app_directories: &AppDirectories,
+    asset: &Asset,
+    use_user_directory: bool,
+    allow_downgrade: bool,
+    copy_user_data: bool,
+) -> Result<ExitStatus, Error> {
+    let installation_path = asset
+        .game_edition_directory_path
+        .join(settings.decompressed_game_directory_path.as_path());
+    let launcher_path = installation_path.join(settings.launcher_name.as_str());
+
+    let mut command = process::Command::new(&launcher_path);
+    let profile = settings
+        .user_directory_profile_of(asset)
+        .filter(|_| use_user_directory);
+    let user_directory_path = profile
+        .as_deref()
+        .map(|profile| {
+            prepare_user_directory(app_directories, profile, &installation_path, copy_user_data)
+        })
+        .transpose()?;
+    let location = match &profile {
+        Some(profile) => UserDataLocation::UserDirectory(profile.to_string()),
+        None => UserDataLocation::Installation(InstallationId::from(asset)),
+    };
+    let user_data_path = user_directory_path
+        .clone()
+        .unwrap_or_else(|| installation_path.clone());
+
+    if !allow_downgrade {
+        protect_from_downgrade(settings, app_directories, asset, &location, &user_data_path)?;
+    }
+    if settings.backup_policy.before_launch {
+        back_up_changed_worlds(settings, app_directories, &location)?;
+    }
+
+    match profile.zip(user_directory_path) {
+        Some((profile, user_directory_path)) => {
+            println!(
+                "Running {} with the {} user directory",
+                &launcher_path.display(),
+                profile
+            );
+            // The game appends file names right after this path.
+            let mut user_directory_argument = user_directory_path.into_os_string();
+            user_directory_argument.push("/");
+            command.arg("--userdir").arg(user_directory_argument);
+        }
+        None => println!("Running {}", &launcher_path.display()),
+    }
+
+    let started_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let exit_status = command
+        .spawn()
+        .with_context(|| format!("Failed to launch {}", settings.launcher_name.as_str()))?
+        .wait()?;
+    record_world_usages(
+        settings,
+        app_directories,
+        asset,
+        &user_data_path,
+        started_at,
+    )?;
+    record_option_names(app_directories, asset, &user_data_path, started_at)?;
+
+    Ok(exit_status)
+}
@@ -0,0 +1,118 @@
+use anyhow::{anyhow, Error};
+use cdda_cat_data::entities::{Asset, InstallationId};
+use cdda_cat_lib::installation_manager::AppSettings;
+use derive_more::Display;
+use std::path::PathBuf;
+
+use crate::infra::fs::app_directories::{user_directory_profile, AppDirectories};
+
+pub const USER_DIRECTORY_PREFIX: &str = "userdir:";
+
+/// A directory the game keeps `save/`, `config/` and the rest of its user
+/// data in.
+#[derive(Debug, Clone, PartialEq, Display)]
+pub enum UserDataLocation {
+    #[display(fmt = "{}", _0)]
+    Installation(InstallationId),
+    #[display(fmt = "the {} user directory", _0)]
+    UserDirectory(String),
+}
+
+/// Written as `userdir:PROFILE`, otherwise like an `InstallationId`. Profiles
+/// have to be plain directory names, since they end up in paths.
+impl ::core::str::FromStr for UserDataLocation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.strip_prefix(USER_DIRECTORY_PREFIX) {
+            Some(profile) => {
+                UserDataLocation::UserDirectory(user_directory_profile(profile)?.to_string())
+            }
+            None => UserDataLocation::Installation(InstallationId::from_str(s)?),
+        })
+    }
+}
+
+impl UserDataLocation {
+    /// Where the game of `asset` keeps its user data when launched.
+    pub fn of_installation(settings: &AppSettings, asset: &Asset) -> Self {
+        match settings.user_directory_profile_of(asset) {
+            Some(profile) => UserDataLocation::UserDirectory(profile),
+            None => UserDataLocation::Installation(InstallationId::from(asset)),
+        }
+    }
+
+    /// The game directory of a registered installation or an existing user
+    /// directory.
+    pub fn resolve(
+        &self,
+        settings: &AppSettings,
+        app_directories: &AppDirectories,
+    ) -> Result<PathBuf, Error> {
+        match self {
+            UserDataLocation::Installation(installation) => settings
+                .find_installation(installation)
+                .map(|asset| settings.installation_path_of(asset))
+                .ok_or_else(|| anyhow!("{} is not installed!", installation)),
+            UserDataLocation::UserDirectory(profile) => {
+                let user_directory_path = app_directories.user_directory_path(profile)?;
+                if user_directory_path.is_dir() {
+                    Ok(user_directory_path)
+                } else {
+                    Err(anyhow!("There is no {} user directory!", profile))
+                }
+            }
+        }
+    }
+
+    /// Registered installations whose game keeps its user data here.
+    pub fn installations<'a>(&self, settings: &'a AppSettings) -> Vec<&'a Asset> {
+        match self {
+            UserDataLocation::Installation(installation) => settings
+                .find_installation(installation)
+                .into_iter()
+                .collect(),
+            UserDataLocation::UserDirectory(profile) => settings
+                .installed_games
+                .iter()
+                .filter(|asset| settings.user_directory_profile_of(asset).as_ref() == Some(profile))
+                .collect(),
+        }
+    }
+
+    /// Where backups of this location go, relative to the backups directory.
+    pub fn backup_directory_path(&self) -> PathBuf {
+        match self {
+            UserDataLocation::Installation(installation) => PathBuf::from("installations")
+                .join(format!("{}-{}", installation.tag, installation.edition)),
+            UserDataLocation::UserDirectory(profile) => PathBuf::from("userdirs").join(profile),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn refuses_profiles_that_are_not_plain_directory_names() {
+        assert_eq!(
+            UserDataLocation::from_str("userdir:default").unwrap(),
+            UserDataLocation::UserDirectory("default".to_string())
+        );
+        for location in [
+            "userdir:../..",
+            "userdir:a/b",
+            "userdir:/etc",
+            "userdir:",
+            "userdir:.",
+        ] {
+            assert!(
+                UserDataLocation::from_str(location).is_err(),
+                "{}",
+                location
+            );
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use anyhow::{anyhow, Error};
+use cdda_cat_lib::installation_manager::AppSettings;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::infra::fs::user_data::{
+    find_user_added_content, list_content_entries, list_user_data_entries, migrate_entry,
+    read_shipped_content, ConflictResolution, MigrationOutcome,
+};
+
+use crate::infra::fs::app_directories::AppDirectories;
+
+use super::backup::back_up_location_before_changing;
+use super::location::UserDataLocation;
+use super::prompt::ask;
+
+#[derive(Debug, Clone, Default)]
+pub struct MigrateOptions {
+    /// Removes entries from the source once they are migrated.
+    pub move_entries: bool,
+    /// Asks on the terminal when unset.
+    pub on_conflict: Option<ConflictResolution>,
+}
+
+fn ask_conflict_resolution() -> Result<ConflictResolution, Error> {
+    let answer = ask("Overwrite, skip or rename them? [o/s/r/N]", "--on_conflict")?;
+    if answer.is_empty() || answer.to_lowercase().starts_with('n') {
+        return Err(anyhow!("Migration cancelled, nothing was changed"));
+    }
+
+    ConflictResolution::from_str(&answer)
+}
+
+/// One end of a migration: where its game keeps user data, and for
+/// installations the game directory players add mods, tilesets and sound
+/// packs to.
+struct MigrationSide {
+    user_data_location: UserDataLocation,
+    user_data_path: PathBuf,
+    installation_path: Option<PathBuf>,
+}
+
+impl MigrationSide {
+    fn resolve(
+        settings: &AppSettings,
+        app_directories: &AppDirectories,
+        location: &UserDataLocation,
+    ) -> Result<Self, Error> {
+        let (user_data_location, installation_path) = match location {
+            UserDataLocation::Installation(installation) => {
+                let asset = settings
+                    .find_installation(installation)
+                    .ok_or_else(|| anyhow!("{} is not installed!", installation))?;
+                (
+                    UserDataLocation::of_installation(settings, asset),
+                    Some(settings.installation_path_of(asset)),
+                )
+            }
+            UserDataLocation::UserDirectory(_) => (location.clone(), None),
+        };
+
+        Ok(Self {
+            user_data_path: user_data_location.resolve(settings, app_directories)?,
+            user_data_location,
+            installation_path,
+        })
+    }
+
+    /// Added content lives in the installation, or in the user directory for
+    /// those that aren't one.
+    fn content_path(&self) -> &Path {
+        self.installation_path
+            .as_deref()
+            .unwrap_or(&self.user_data_path)
+    }
+}
+
+/// Copies or moves saves, configuration and user-added content of the `from`
+/// location into the `to` one. Installations migrate the user directory
+/// their game uses along with the content added to their game directory.
+pub fn migrate(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    from: &UserDataLocation,
+    to: &UserDataLocation,
+    options: &MigrateOptions,
+) -> Result<(), Error> {
+    let source = MigrationSide::resolve(settings, app_directories, from)?;
+    let destination = MigrationSide::resolve(settings, app_directories, to)?;
+    if source.content_path() == destination.content_path()
+        && source.user_data_path == destination.user_data_path
+    {
+        return Err(anyhow!("Can't migrate {} onto itself!", from));
+    }
+
+    // Relative paths along with the directories they are relative to.
+    let mut entries: Vec<(PathBuf, &Path, &Path)> = Vec::new();
+    if source.user_data_path == destination.user_data_path {
+        println!(
+            "{} and {} share {}, so only added content gets migrated.",
+            from, to, source.user_data_location
+        );
+    } else {
+        for entry in list_user_data_entries(&source.user_data_path)? {
+            entries.push((entry, &source.user_data_path, &destination.user_data_path));
+        }
+    }
+    if source.content_path() != destination.content_path() {
+        let added_content = match &source.installation_path {
+            Some(installation_path) => {
+                if read_shipped_content(installation_path)?.is_none()
+                    && destination.installation_path.is_none()
+                {
+                    println!(
+                        "{} has no record of the content it shipped, so none of its mods, tilesets or sound packs are migrated.",
+                        from
+                    );
+                }
+                find_user_added_content(
+                    installation_path,
+                    destination.installation_path.as_deref(),
+                )?
+            }
+            None => list_content_entries(&source.user_data_path)?,
+        };
+        for entry in added_content {
+            entries.push((entry, source.content_path(), destination.content_path()));
+        }
+    }
+    if entries.is_empty() {
+        println!("{} has no user data to migrate.", from);
+        return Ok(());
+    }
+
+    let conflicts: Vec<&PathBuf> = entries
+        .iter()
+        .filter(|(entry, _, destination_path)| {
+            fs::symlink_metadata(destination_path.join(entry)).is_ok()
+        })
+        .map(|(entry, _, _)| entry)
+        .collect();
+    let resolution = match options.on_conflict {
+        _ if conflicts.is_empty() => ConflictResolution::Skip,
+        Some(resolution) => resolution,
+        None => {
+            println!("{} already has:", to);
+            for conflict in conflicts.iter() {
+                println!("  {}", conflict.display());
+            }
+            ask_conflict_resolution()?
+        }
+    };
+
+    back_up_location_before_changing(settings, app_directories, &destination.user_data_location)?;
+    if options.move_entries {
+        back_up_location_before_changing(settings, app_directories, &source.user_data_location)?;
+    }
+
+    let verb = if options.move_entries {
+        "Moved"
+    } else {
+        "Copied"
+    };
+    let (mut migrated, mut overwritten, mut skipped, mut renamed) = (0, 0, 0, 0);
+    for (entry, source_path, destination_path) in entries.iter() {
+        match migrate_entry(
+            source_path,
+            destination_path,
+            entry,
+            resolution,
+            options.move_entries,
+        )? {
+            MigrationOutcome::Migrated => migrated += 1,
+            MigrationOutcome::Overwritten => {
+                println!("Overwrote {}", entry.display());
+                overwritten += 1;
+            }
+            MigrationOutcome::Skipped => {
+                println!("Skipped {}, {} already has it", entry.display(), to);
+                skipped += 1;
+            }
+            MigrationOutcome::Renamed(renamed_path) => {
+                println!("{} {} as {}", verb, entry.display(), renamed_path.display());
+                renamed += 1;
+            }
+        }
+    }
+
+    println!(
+        "{} {} entries from {} to {}: {} new, {} overwritten, {} renamed, {} skipped.",
+        verb,
+        migrated + overwritten + renamed,
+        from,
+        to,
+        migrated,
+        overwritten,
+        renamed,
+        skipped
+    );
+
+    Ok(())
+}
@@ -1,2 +1,23 @@
+pub mod backup;
+pub mod cache;
 pub mod download_archive;
+pub mod favorite;
+pub mod game_config;
+pub mod gc;
+pub mod install;
+pub mod keybindings;
+pub mod launch;
+pub mod location;
+pub mod migrate;
+pub mod plan;
 pub mod progress_bar;
+pub mod prompt;
+pub mod prune;
+pub mod selection;
+pub mod stream_archive;
+pub mod subscription;
+pub mod uninstall;
+pub mod update;
+pub mod userdir;
+pub mod world;
+pub mod worlds;
@@ -0,0 +1,117 @@
+use anyhow::Error;
+use cdda_cat_data::entities::InstallationId;
+use indicatif::HumanBytes;
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedDownload {
+    pub url: String,
+    pub path: PathBuf,
+    /// Zero when the archive was downloaded before.
+    pub bytes: u64,
+}
+
+/// Everything a command would change on disk and in the settings file,
+/// printed instead of done with `--dry-run`.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Plan {
+    /// Why the command would stop before changing anything.
+    pub refusals: Vec<String>,
+    pub downloads: Vec<PlannedDownload>,
+    pub created_directories: Vec<PathBuf>,
+    pub replaced_directories: Vec<PathBuf>,
+    /// Archives user data gets saved to before its installation is removed.
+    pub preserved_user_data: Vec<PathBuf>,
+    pub removed_directories: Vec<PathBuf>,
+    pub trashed_paths: Vec<PathBuf>,
+    pub removed_files: Vec<PathBuf>,
+    pub registered_installations: Vec<InstallationId>,
+    pub unregistered_installations: Vec<InstallationId>,
+}
+
+impl Plan {
+    pub fn extend(&mut self, other: Plan) {
+        self.refusals.extend(other.refusals);
+        self.downloads.extend(other.downloads);
+        self.created_directories.extend(other.created_directories);
+        self.replaced_directories.extend(other.replaced_directories);
+        self.preserved_user_data.extend(other.preserved_user_data);
+        self.removed_directories.extend(other.removed_directories);
+        self.trashed_paths.extend(other.trashed_paths);
+        self.removed_files.extend(other.removed_files);
+        self.registered_installations
+            .extend(other.registered_installations);
+        self.unregistered_installations
+            .extend(other.unregistered_installations);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.refusals.is_empty()
+            && self.downloads.is_empty()
+            && self.created_directories.is_empty()
+            && self.replaced_directories.is_empty()
+            && self.preserved_user_data.is_empty()
+            && self.removed_directories.is_empty()
+            && self.trashed_paths.is_empty()
+            && self.removed_files.is_empty()
+            && self.registered_installations.is_empty()
+            && self.unregistered_installations.is_empty()
+    }
+
+    pub fn print(&self, as_json: bool) -> Result<(), Error> {
+        if as_json {
+            println!("{}", serde_json::to_string_pretty(self)?);
+            return Ok(());
+        }
+
+        println!("Dry run, nothing was changed.");
+        if self.is_empty() {
+            println!("Nothing to do.");
+            return Ok(());
+        }
+        for refusal in &self.refusals {
+            println!("Refuse: {}", refusal);
+        }
+        for download in &self.downloads {
+            println!(
+                "Download {} ({}) to {}",
+                download.url,
+                HumanBytes(download.bytes),
+                download.path.display()
+            );
+        }
+        if !self.downloads.is_empty() {
+            println!(
+                "Download {} in total",
+                HumanBytes(self.downloads.iter().map(|download| download.bytes).sum())
+            );
+        }
+        for path in &self.created_directories {
+            println!("Create {}", path.display());
+        }
+        for path in &self.replaced_directories {
+            println!("Replace {}", path.display());
+        }
+        for path in &self.preserved_user_data {
+            println!("Save user data to {}", path.display());
+        }
+        for path in &self.removed_directories {
+            println!("Remove directory {}", path.display());
+        }
+        for path in &self.trashed_paths {
+            println!("Move {} to the trash", path.display());
+        }
+        for path in &self.removed_files {
+            println!("Remove file {}", path.display());
+        }
+        for installation_id in &self.registered_installations {
+            println!("Register {}", installation_id);
+        }
+        for installation_id in &self.unregistered_installations {
+            println!("Unregister {}", installation_id);
+        }
+
+        Ok(())
+    }
+}
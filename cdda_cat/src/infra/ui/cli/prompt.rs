@@ -0,0 +1,33 @@
+use anyhow::{anyhow, Error};
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Asks `question` on the terminal and returns the trimmed answer. Fails when
+/// nobody is there to answer, pointing scripts at `flag` instead.
+pub fn ask(question: &str, flag: &str) -> Result<String, Error> {
+    if !io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "Can't ask for confirmation without a terminal, pass {} to go ahead",
+            flag
+        ));
+    }
+
+    print!("{} ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+
+    Ok(answer.trim().to_string())
+}
+
+/// Asks a yes or no question on the terminal, defaulting to no. Fails when
+/// nobody is there to answer so scripts have to pass `--yes` instead.
+pub fn confirm(question: &str) -> Result<bool, Error> {
+    confirm_or_pass(question, "--yes")
+}
+
+/// Like `confirm`, for commands where `flag` stands in for the answer.
+pub fn confirm_or_pass(question: &str, flag: &str) -> Result<bool, Error> {
+    let answer = ask(&format!("{} [y/N]", question), flag)?;
+
+    Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
@@ -0,0 +1,144 @@
+use anyhow::{anyhow, Error};
+use cdda_cat_data::entities::{Asset, InstallationId};
+use cdda_cat_lib::installation_manager::AppSettings;
+
+use crate::infra::fs::app_directories::AppDirectories;
+
+use super::plan::Plan;
+use super::prompt::confirm;
+use super::uninstall::{plan_removal, remove_installation, RemovalOptions};
+
+/// Installations beyond the `keep_newest` newest of each edition, apart from
+/// `just_installed` and protected installations.
+fn select_installations(
+    settings: &AppSettings,
+    keep_newest: usize,
+    just_installed: Option<&Asset>,
+) -> Vec<Asset> {
+    settings
+        .select_installations_to_prune(keep_newest)
+        .into_iter()
+        .filter(|asset| Some(asset) != just_installed)
+        .collect()
+}
+
+pub fn plan_prune(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    keep_newest: usize,
+    just_installed: Option<&Asset>,
+    removal_options: &RemovalOptions,
+) -> Result<Plan, Error> {
+    let mut plan = Plan::default();
+    for asset in select_installations(settings, keep_newest, just_installed) {
+        plan.extend(plan_removal(
+            settings,
+            app_directories,
+            &asset,
+            removal_options,
+        )?);
+    }
+
+    Ok(plan)
+}
+
+/// Uninstalls everything `select_installations` picks, once confirmed unless
+/// `assume_yes`.
+pub fn prune_installations(
+    settings: &mut AppSettings,
+    app_directories: &AppDirectories,
+    keep_newest: usize,
+    just_installed: Option<&Asset>,
+    removal_options: &RemovalOptions,
+    assume_yes: bool,
+) -> Result<(), Error> {
+    let installations = select_installations(settings, keep_newest, just_installed);
+    if installations.is_empty() {
+        println!(
+            "Nothing to prune, at most {} installations per edition are kept.",
+            keep_newest
+        );
+        return Ok(());
+    }
+
+    println!(
+        "Keeping the {} newest installations per edition and channel, removing:",
+        keep_newest
+    );
+    for asset in &installations {
+        println!("  {}", InstallationId::from(asset));
+    }
+    if !assume_yes
+        && !confirm(&format!(
+            "Remove these {} installations?",
+            installations.len()
+        ))?
+    {
+        println!("Aborted, nothing was removed.");
+        return Ok(());
+    }
+    for asset in &installations {
+        remove_installation(settings, app_directories, asset, removal_options)?;
+    }
+    println!("Pruned {} installations.", installations.len());
+
+    Ok(())
+}
+
+/// The configured limit if the policy asks for pruning after installs.
+fn keep_newest_after_install(settings: &AppSettings) -> Option<usize> {
+    let policy = &settings.installation_retention_policy;
+    policy.keep_newest.filter(|_| policy.prune_after_install)
+}
+
+pub fn plan_prune_after_install(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    just_installed: Option<&Asset>,
+) -> Result<Plan, Error> {
+    match keep_newest_after_install(settings) {
+        Some(keep_newest) => plan_prune(
+            settings,
+            app_directories,
+            keep_newest,
+            just_installed,
+            &RemovalOptions::configured(settings),
+        ),
+        None => Ok(Plan::default()),
+    }
+}
+
+/// Prunes with the configured policy if it asks for pruning after installs,
+/// which doesn't ask since the policy already said so.
+pub fn prune_after_install(
+    settings: &mut AppSettings,
+    app_directories: &AppDirectories,
+    just_installed: Option<&Asset>,
+) -> Result<(), Error> {
+    match keep_newest_after_install(settings) {
+        Some(keep_newest) => {
+            let removal_options = RemovalOptions::configured(settings);
+            prune_installations(
+                settings,
+                app_directories,
+                keep_newest,
+                just_installed,
+                &removal_options,
+                true,
+            )
+        }
+        None => Ok(()),
+    }
+}
+
+/// `keep_newest` or else the configured limit.
+pub fn resolve_keep_newest(
+    settings: &AppSettings,
+    keep_newest: Option<usize>,
+) -> Result<usize, Error> {
+    keep_newest
+        .or(settings.installation_retention_policy.keep_newest)
+        .ok_or_else(|| {
+            anyhow!("Pass --keep or set installation_retention_policy.keep_newest in settings!")
+        })
+}
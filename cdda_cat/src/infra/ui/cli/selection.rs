@@ -0,0 +1,151 @@
+use anyhow::{Context, Error};
+use cdda_cat_data::entities::{Asset, Channel, Edition};
+use cdda_cat_lib::installation_manager::AppSettings;
+use cdda_cat_lib::tag_ordering::compare_tags;
+use chrono::{DateTime, Local, NaiveDate};
+use derive_more::Display;
+use glob::Pattern;
+use std::cmp::Ordering;
+use std::fs;
+
+#[derive(Debug, Clone, Display)]
+pub enum EditionFilter {
+    #[display(fmt = "any")]
+    Any,
+    #[display(fmt = "{}", _0)]
+    Only(Edition),
+}
+
+impl ::core::str::FromStr for EditionFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.to_lowercase() == "any" {
+            Ok(EditionFilter::Any)
+        } else {
+            Ok(EditionFilter::Only(Edition::from_str(s)?))
+        }
+    }
+}
+
+impl EditionFilter {
+    fn matches(&self, edition: &Edition) -> bool {
+        match self {
+            EditionFilter::Any => true,
+            EditionFilter::Only(only) => only == edition,
+        }
+    }
+}
+
+/// A `YYYY-MM-DD` date or a release tag, which only tags of its own channel
+/// can be older than.
+#[derive(Debug, Clone)]
+pub enum OlderThan {
+    Date(NaiveDate),
+    Tag(String),
+}
+
+impl ::core::str::FromStr for OlderThan {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            Ok(date) => OlderThan::Date(date),
+            Err(_) => OlderThan::Tag(s.to_string()),
+        })
+    }
+}
+
+/// Build date of experimental tags like `cdda-experimental-2024-10-17-0123`.
+pub fn date_in_tag(tag: &str) -> Option<NaiveDate> {
+    let chunks: Vec<&str> = tag.split('-').collect();
+    chunks.windows(3).find_map(|chunk| match chunk {
+        [year, month, day] if year.len() == 4 => {
+            NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+        }
+        _ => None,
+    })
+}
+
+/// The build date in its tag, or else the day it was installed.
+fn installation_date(settings: &AppSettings, asset: &Asset) -> Result<NaiveDate, Error> {
+    if let Some(date) = date_in_tag(&asset.tag) {
+        return Ok(date);
+    }
+
+    let installation_path = asset
+        .game_edition_directory_path
+        .join(settings.decompressed_game_directory_path.as_path());
+    let metadata = fs::metadata(&installation_path)
+        .with_context(|| format!("Failed to read {}", installation_path.display()))?;
+    let installed_at = metadata.created().or_else(|_| metadata.modified())?;
+
+    Ok(DateTime::<Local>::from(installed_at).date_naive())
+}
+
+/// Picks installed games to act on in bulk. Every given criterion has to
+/// match.
+#[derive(Debug, Clone)]
+pub struct InstallationFilter {
+    pub tag_pattern: Option<String>,
+    pub edition: EditionFilter,
+    pub older_than: Option<OlderThan>,
+    /// Leave out the newest installation of each edition and channel.
+    pub all_except_latest: bool,
+}
+
+impl InstallationFilter {
+    /// Whether anything narrows the selection down beyond the edition.
+    pub fn is_narrowed(&self) -> bool {
+        self.tag_pattern.is_some() || self.older_than.is_some() || self.all_except_latest
+    }
+
+    /// Whether this may pick more than the one installation named exactly.
+    pub fn is_bulk(&self) -> bool {
+        let is_exact_tag = self
+            .tag_pattern
+            .as_deref()
+            .is_some_and(|tag_pattern| Pattern::escape(tag_pattern) == tag_pattern);
+
+        !is_exact_tag
+            || self.older_than.is_some()
+            || self.all_except_latest
+            || matches!(self.edition, EditionFilter::Any)
+    }
+
+    fn is_older(&self, settings: &AppSettings, asset: &Asset) -> Result<bool, Error> {
+        Ok(match &self.older_than {
+            None => true,
+            Some(OlderThan::Tag(tag)) => compare_tags(&asset.tag, tag) == Some(Ordering::Less),
+            Some(OlderThan::Date(date)) => installation_date(settings, asset)? < *date,
+        })
+    }
+
+    pub fn select(&self, settings: &AppSettings) -> Result<Vec<Asset>, Error> {
+        let tag_pattern = self
+            .tag_pattern
+            .as_deref()
+            .map(Pattern::new)
+            .transpose()
+            .context("Invalid tag pattern")?;
+
+        let mut selected = Vec::new();
+        for asset in settings.installed_games.iter() {
+            let is_match = self.edition.matches(&asset.edition)
+                && tag_pattern
+                    .as_ref()
+                    .is_none_or(|pattern| pattern.matches(&asset.tag))
+                && (!self.all_except_latest
+                    || settings
+                        .installed_games_of(&asset.edition, Channel::from(asset.tag.as_str()))
+                        .first()
+                        != Some(&asset))
+                && self.is_older(settings, asset)?;
+            if is_match {
+                selected.push(asset.clone());
+            }
+        }
+
+        Ok(selected)
+    }
+}
@@ -0,0 +1,52 @@
+use anyhow::{anyhow, Error};
+use cdda_cat_data::entities::ArchiveFilePath;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::infra::net::channel_reader::ChannelReader;
+use crate::infra::net::download::DownloadInfo;
+use crate::infra::ui::cli::download_archive::download_progress_bar;
+use crate::infra::ui::cli::progress_bar::ProgressBarInfo;
+
+/// How many downloaded chunks may wait for the unpacker before the download
+/// waits for it instead.
+const BUFFERED_CHUNKS: usize = 64;
+
+/// Starts downloading `from_url` in the background and hands back a reader
+/// over the bytes as they arrive, plus the download task itself. With
+/// `keep_archive`, the archive also ends up at `archive_path` once complete.
+pub async fn stream_archive(
+    from_url: &str,
+    archive_path: &ArchiveFilePath,
+    keep_archive: bool,
+) -> Result<(ChannelReader, JoinHandle<Result<(), Error>>), Error> {
+    let download_info = DownloadInfo::new(from_url, archive_path.to_path_buf());
+    let progress_bar_info = ProgressBarInfo::new(
+        &format!("Downloading and unpacking from {}", &download_info.url),
+        &format!("Finished downloading from {}", &download_info.url),
+    );
+
+    let response = download_info
+        .client
+        .get(&download_info.url)
+        .send()
+        .await
+        .or(Err(anyhow!("Failed to GET from '{}'", download_info.url)))?;
+    let total_length = response.content_length().ok_or(anyhow!(
+        "Failed to get content length from '{}'",
+        &download_info.url
+    ))?;
+
+    let progress_bar = download_progress_bar(total_length);
+    progress_bar.set_message(progress_bar_info.pre_message.to_string());
+    let (sender, receiver) = mpsc::channel(BUFFERED_CHUNKS);
+    let download_task = tokio::spawn(async move {
+        download_info
+            .stream_to_channel(&progress_bar, response, sender, keep_archive)
+            .await?;
+        progress_bar.set_message(progress_bar_info.post_message.to_string());
+        Ok(())
+    });
+
+    Ok((ChannelReader::new(receiver), download_task))
+}
@@ -0,0 +1,78 @@
+use anyhow::{anyhow, Error};
+use cdda_cat_data::entities::{Channel, Edition};
+use cdda_cat_lib::installation_manager::AppSettings;
+use std::path::Path;
+
+/// Makes `update` and `latest` follow `channel` for `edition`.
+pub fn subscribe(
+    settings: &mut AppSettings,
+    settings_file_path: &Path,
+    edition: &Edition,
+    channel: Channel,
+) -> Result<(), Error> {
+    let mut subscription = settings.subscription_of(edition);
+    subscription.channel = channel;
+    let pinned_tag = subscription.pinned_tag.clone();
+    settings.set_subscription(subscription);
+    settings.write_to_file(settings_file_path)?;
+
+    println!("Edition {} now follows {} releases.", edition, channel);
+    if let Some(pinned_tag) = pinned_tag {
+        println!("It stays on {} until unpinned.", pinned_tag);
+    }
+
+    Ok(())
+}
+
+/// Keeps `edition` on `release_tag`, or on its newest installation in the
+/// subscribed channel when no tag is given.
+pub fn pin(
+    settings: &mut AppSettings,
+    settings_file_path: &Path,
+    edition: &Edition,
+    release_tag: Option<String>,
+) -> Result<(), Error> {
+    let release_tag = match release_tag {
+        Some(release_tag) => release_tag,
+        None => settings
+            .installed_games_of(edition, settings.subscription_of(edition).channel)
+            .first()
+            .map(|asset| asset.tag.to_string())
+            .ok_or_else(|| {
+                anyhow!(
+                    "No installation of edition {} to pin, pass --tag instead!",
+                    edition
+                )
+            })?,
+    };
+
+    let mut subscription = settings.subscription_of(edition);
+    subscription.pinned_tag = Some(release_tag.to_string());
+    settings.set_subscription(subscription);
+    settings.write_to_file(settings_file_path)?;
+    println!("Pinned edition {} to {}", edition, release_tag);
+
+    Ok(())
+}
+
+pub fn unpin(
+    settings: &mut AppSettings,
+    settings_file_path: &Path,
+    edition: &Edition,
+) -> Result<(), Error> {
+    let mut subscription = settings.subscription_of(edition);
+    match subscription.pinned_tag.take() {
+        Some(pinned_tag) => {
+            let channel = subscription.channel;
+            settings.set_subscription(subscription);
+            settings.write_to_file(settings_file_path)?;
+            println!(
+                "Unpinned edition {} from {}, it follows {} releases again.",
+                edition, pinned_tag, channel
+            );
+        }
+        None => println!("Edition {} isn't pinned.", edition),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,244 @@
+use anyhow::{anyhow, Context, Error};
+use cdda_cat_data::entities::{Asset, InstallationId};
+use cdda_cat_lib::installation_manager::AppSettings;
+use chrono::Local;
+use std::path::PathBuf;
+
+use crate::infra::fs::app_directories::AppDirectories;
+use crate::infra::fs::trash::discard;
+use crate::infra::fs::user_data::{archive_user_data, find_user_data};
+
+use super::backup::back_up_before_changing;
+use super::plan::Plan;
+use super::prompt::confirm;
+use super::selection::InstallationFilter;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RemovalOptions {
+    pub remove_cdda_dir_only: bool,
+    /// Remove saves and configuration too instead of archiving them first.
+    pub purge: bool,
+    pub use_trash: bool,
+}
+
+impl RemovalOptions {
+    /// What removals do unless the command line says otherwise.
+    pub fn configured(settings: &AppSettings) -> Self {
+        Self {
+            use_trash: settings.move_to_trash,
+            ..Self::default()
+        }
+    }
+}
+
+/// The directory uninstalling `asset` removes.
+fn removed_directory_path(
+    settings: &AppSettings,
+    asset: &Asset,
+    options: &RemovalOptions,
+) -> PathBuf {
+    let game_edition_directory_path = asset.game_edition_directory_path.to_path_buf();
+    if options.remove_cdda_dir_only {
+        game_edition_directory_path.join(settings.decompressed_game_directory_path.as_path())
+    } else {
+        game_edition_directory_path
+    }
+}
+
+/// Where the user data of `asset` gets archived before it is removed, along
+/// with the user data directories found in it.
+fn preserved_user_data(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    asset: &Asset,
+    options: &RemovalOptions,
+) -> Result<Option<(PathBuf, Vec<PathBuf>)>, Error> {
+    if options.purge {
+        return Ok(None);
+    }
+
+    let installation_path = asset
+        .game_edition_directory_path
+        .join(settings.decompressed_game_directory_path.as_path());
+    let user_data_paths = find_user_data(&installation_path)?;
+    if user_data_paths.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some((
+        user_data_archive_path(app_directories, &format!("{}-{}", asset.tag, asset.edition)),
+        user_data_paths,
+    )))
+}
+
+/// Where user data of the installation called `name` gets archived to when
+/// it is removed now.
+pub fn user_data_archive_path(app_directories: &AppDirectories, name: &str) -> PathBuf {
+    let archive_name = format!("{}-{}.tar.gz", name, Local::now().format("%Y%m%d-%H%M%S"));
+
+    app_directories
+        .preserved_user_data_path()
+        .join(archive_name)
+}
+
+pub fn plan_removal(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    asset: &Asset,
+    options: &RemovalOptions,
+) -> Result<Plan, Error> {
+    let mut plan = Plan {
+        preserved_user_data: preserved_user_data(settings, app_directories, asset, options)?
+            .map(|(archive_path, _)| archive_path)
+            .into_iter()
+            .collect(),
+        unregistered_installations: vec![InstallationId::from(asset)],
+        ..Plan::default()
+    };
+    let removed_directory_path = removed_directory_path(settings, asset, options);
+    if options.use_trash {
+        plan.trashed_paths.push(removed_directory_path);
+    } else {
+        plan.removed_directories.push(removed_directory_path);
+    }
+
+    Ok(plan)
+}
+
+/// Archives the user data of `asset` unless purging, then removes its files
+/// and drops it from the installed games and every setting referring to it.
+pub fn remove_installation(
+    settings: &mut AppSettings,
+    app_directories: &AppDirectories,
+    asset: &Asset,
+    options: &RemovalOptions,
+) -> Result<(), Error> {
+    if !options.purge {
+        back_up_before_changing(settings, app_directories, &InstallationId::from(asset))?;
+    }
+    if let Some((archive_path, user_data_paths)) =
+        preserved_user_data(settings, app_directories, asset, options)?
+    {
+        archive_user_data(
+            &asset
+                .game_edition_directory_path
+                .join(settings.decompressed_game_directory_path.as_path()),
+            &user_data_paths,
+            &archive_path,
+        )?;
+        println!(
+            "Saved {} of {} to {}",
+            user_data_paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            InstallationId::from(asset),
+            archive_path.display()
+        );
+    }
+
+    let removed_directory_path = removed_directory_path(settings, asset, options);
+    discard(&removed_directory_path, options.use_trash).with_context(|| {
+        if options.remove_cdda_dir_only {
+            format!(
+                "Failed to remove CDDA's installation directory at: {}",
+                removed_directory_path.display()
+            )
+        } else {
+            format!(
+                "Failed to remove installation directory path of: {}",
+                removed_directory_path.display()
+            )
+        }
+    })?;
+
+    let installation_id = InstallationId::from(asset);
+    if settings
+        .default_installation
+        .as_ref()
+        .is_some_and(|default| default.matches(asset))
+    {
+        println!(
+            "{} was the default installation, choose another one with default",
+            installation_id
+        );
+    }
+    if settings
+        .subscription_of(&asset.edition)
+        .pinned_tag
+        .as_deref()
+        == Some(asset.tag.as_str())
+    {
+        println!("Unpinned edition {} from {}", asset.edition, asset.tag);
+    }
+    settings.forget_installation(asset);
+    settings.write_to_file(&app_directories.settings_file_path)
+}
+
+fn select(settings: &AppSettings, filter: &InstallationFilter) -> Result<Vec<Asset>, Error> {
+    if !filter.is_narrowed() {
+        return Err(anyhow!(
+            "Pass --tag, --older-than or --all-except-latest to choose what to uninstall!"
+        ));
+    }
+
+    let assets = filter.select(settings)?;
+    if assets.is_empty() {
+        return Err(match (&filter.tag_pattern, filter.is_bulk()) {
+            (Some(release_tag), false) => anyhow!(
+                "No asset with release tag of {} and edition of {} was found to uninstall!",
+                release_tag,
+                filter.edition
+            ),
+            _ => anyhow!("No installed game matches the given criteria!"),
+        });
+    }
+
+    Ok(assets)
+}
+
+pub fn plan_uninstall(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    filter: &InstallationFilter,
+    options: &RemovalOptions,
+) -> Result<Plan, Error> {
+    let mut plan = Plan::default();
+    for asset in select(settings, filter)? {
+        plan.extend(plan_removal(settings, app_directories, &asset, options)?);
+    }
+
+    Ok(plan)
+}
+
+/// Removes every installation `filter` selects, asking first when it may
+/// select more than one unless `assume_yes` is set. Returns how many were
+/// removed.
+pub fn uninstall(
+    settings: &mut AppSettings,
+    app_directories: &AppDirectories,
+    filter: &InstallationFilter,
+    options: &RemovalOptions,
+    assume_yes: bool,
+) -> Result<usize, Error> {
+    let assets = select(settings, filter)?;
+    println!("Found {} matching installations:", assets.len());
+    for asset in &assets {
+        println!("  {}", InstallationId::from(asset));
+    }
+    if filter.is_bulk()
+        && !assume_yes
+        && !confirm(&format!("Uninstall these {} installations?", assets.len()))?
+    {
+        println!("Aborted, nothing was removed.");
+        return Ok(0);
+    }
+
+    println!("Uninstalling..");
+    for asset in &assets {
+        remove_installation(settings, app_directories, asset, options)?;
+    }
+
+    Ok(assets.len())
+}
@@ -0,0 +1,182 @@
+use anyhow::{anyhow, Error};
+use cdda_cat_data::entities::{Asset, Channel, Edition, EditionSubscription, InstallationId};
+use cdda_cat_lib::github_client::GithubClient;
+use cdda_cat_lib::installation_manager::{AppSettings, CDDARelease};
+
+use crate::infra::fs::app_directories::AppDirectories;
+use crate::infra::fs::user_data::{carry_over_user_data, read_shipped_content};
+
+use super::backup::back_up_before_changing;
+use super::install::{install, InstallOptions};
+use super::launch::launch_installation;
+use super::uninstall::{remove_installation, RemovalOptions};
+
+/// Tag that `install` resolves through the subscription of the edition.
+pub const LATEST_TAG: &str = "latest";
+
+fn is_candidate(release: &CDDARelease, edition: &Edition, channel: Channel) -> bool {
+    release.channel() == channel && release.linux_asset(edition).is_some()
+}
+
+/// Newest release in `channel` that ships `edition` and was published after
+/// `installed_tag`. Releases are expected newest first.
+pub fn find_newer_release<'a>(
+    releases: &'a [CDDARelease],
+    installed_tag: &str,
+    edition: &Edition,
+    channel: Channel,
+) -> Option<&'a CDDARelease> {
+    let installed_position = releases
+        .iter()
+        .position(|release| release.tag_name == installed_tag)
+        .unwrap_or(releases.len());
+
+    releases[..installed_position]
+        .iter()
+        .find(|release| is_candidate(release, edition, channel))
+}
+
+/// The pinned release of `subscription`, or else the newest one of its
+/// channel.
+pub async fn fetch_latest_release(
+    gh_client: GithubClient,
+    subscription: &EditionSubscription,
+) -> Result<CDDARelease, Error> {
+    if let Some(pinned_tag) = &subscription.pinned_tag {
+        return CDDARelease::fetch_by_tag(gh_client, pinned_tag).await;
+    }
+
+    CDDARelease::fetch_recent(gh_client)
+        .await?
+        .into_iter()
+        .find(|release| is_candidate(release, &subscription.edition, subscription.channel))
+        .ok_or_else(|| {
+            anyhow!(
+                "No recent {} release ships edition {}!",
+                subscription.channel,
+                subscription.edition
+            )
+        })
+}
+
+/// Installs the newest build of `edition` in place of the newest installed
+/// one of the channel and carries its user data over. Returns whether there
+/// was anything to update.
+pub async fn update(
+    settings: &mut AppSettings,
+    app_directories: &AppDirectories,
+    gh_client: GithubClient,
+    edition: &Edition,
+    channel: Option<Channel>,
+    remove_old: bool,
+    install_options: &InstallOptions,
+) -> Result<bool, Error> {
+    let subscription = settings.subscription_of(edition);
+    let channel = channel.unwrap_or(subscription.channel);
+    // Switching channels updates whatever was installed last.
+    let installed_asset: Asset = settings
+        .installed_games_of(edition, channel)
+        .first()
+        .copied()
+        .or_else(|| settings.last_installed_game_of(edition))
+        .cloned()
+        .ok_or_else(|| {
+            anyhow!(
+                "No installation of edition {} was found to update, install one first!",
+                edition
+            )
+        })?;
+    if let Some(pinned_tag) = &subscription.pinned_tag {
+        println!(
+            "Edition {} is pinned to {}, run unpin to update it.",
+            edition, pinned_tag
+        );
+        return Ok(false);
+    }
+    println!(
+        "Looking for {} releases newer than {}..",
+        channel, installed_asset.tag
+    );
+
+    let releases = CDDARelease::fetch_recent(gh_client).await?;
+    let release = match find_newer_release(&releases, &installed_asset.tag, edition, channel) {
+        Some(release) => release,
+        None => {
+            println!(
+                "{} is already the newest {} build.",
+                installed_asset.tag, channel
+            );
+            return Ok(false);
+        }
+    };
+    println!("Updating {} to {}", installed_asset.tag, release.tag_name);
+    back_up_before_changing(
+        settings,
+        app_directories,
+        &InstallationId::from(&installed_asset),
+    )?;
+
+    let new_asset = install(
+        settings,
+        app_directories,
+        release,
+        edition,
+        &InstallOptions {
+            download_only: false,
+            ..install_options.clone()
+        },
+    )
+    .await?;
+
+    if let Some(profile) = settings.inherit_user_directory(&installed_asset, &new_asset) {
+        settings.write_to_file(&app_directories.settings_file_path)?;
+        println!(
+            "{} keeps using the {} user directory",
+            new_asset.tag, profile
+        );
+    }
+
+    let installed_path = settings.installation_path_of(&installed_asset);
+    if read_shipped_content(&installed_path)?.is_none() {
+        println!(
+            "{} has no record of the mods, tilesets and sound packs it shipped, so those missing from {} are carried over as yours.",
+            installed_asset.tag, new_asset.tag
+        );
+    }
+    let copy_report =
+        carry_over_user_data(&installed_path, &settings.installation_path_of(&new_asset))?;
+    println!(
+        "Carried over {} files of user data from {}",
+        copy_report.copied_files, installed_asset.tag
+    );
+    for skipped_path in &copy_report.skipped_paths {
+        eprintln!("Kept existing {}", skipped_path.display());
+    }
+
+    if remove_old {
+        println!(
+            "Launching {} before removing {}..",
+            new_asset.tag, installed_asset.tag
+        );
+        let exit_status =
+            launch_installation(settings, app_directories, &new_asset, true, false, false)?;
+        if !exit_status.success() {
+            return Err(anyhow!(
+                "{} exited with {}, so {} was kept!",
+                new_asset.tag,
+                exit_status,
+                installed_asset.tag
+            ));
+        }
+
+        remove_installation(
+            settings,
+            app_directories,
+            &installed_asset,
+            &RemovalOptions::configured(settings),
+        )?;
+        println!("Removed {}", installed_asset.tag);
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,95 @@
+use anyhow::{anyhow, Error};
+use cdda_cat_data::entities::{Edition, InstallationId, UserDirectoryAssignment};
+use cdda_cat_lib::installation_manager::AppSettings;
+
+use crate::infra::fs::app_directories::AppDirectories;
+use crate::infra::fs::cache::list_directory;
+
+#[derive(Debug, Clone)]
+pub enum UserDirectoryAction {
+    List,
+    Assign {
+        release_tag: String,
+        edition: Edition,
+        /// A profile of its own, named after the installation, when unset.
+        profile: Option<String>,
+    },
+    Unassign {
+        release_tag: String,
+        edition: Edition,
+    },
+}
+
+pub fn userdir(
+    action: UserDirectoryAction,
+    settings: &mut AppSettings,
+    app_directories: &AppDirectories,
+) -> Result<(), Error> {
+    match action {
+        UserDirectoryAction::List => list(settings, app_directories),
+        UserDirectoryAction::Assign {
+            release_tag,
+            edition,
+            profile,
+        } => {
+            let installation = InstallationId::new(&release_tag, &edition);
+            if !settings
+                .installed_games
+                .iter()
+                .any(|asset| installation.matches(asset))
+            {
+                return Err(anyhow!("{} is not installed!", installation));
+            }
+            let profile = profile.unwrap_or_else(|| format!("{}-{}", release_tag, edition));
+            let user_directory_path = app_directories.user_directory_path(&profile)?;
+
+            settings
+                .user_directory_assignments
+                .retain(|assignment| assignment.installation != installation);
+            println!(
+                "{} now uses the {} user directory at {}",
+                installation,
+                profile,
+                user_directory_path.display()
+            );
+            settings
+                .user_directory_assignments
+                .push(UserDirectoryAssignment {
+                    installation,
+                    profile,
+                });
+            settings.write_to_file(&app_directories.settings_file_path)
+        }
+        UserDirectoryAction::Unassign {
+            release_tag,
+            edition,
+        } => {
+            let installation = InstallationId::new(&release_tag, &edition);
+            settings
+                .user_directory_assignments
+                .retain(|assignment| assignment.installation != installation);
+            println!(
+                "{} uses the shared {} user directory again",
+                installation, settings.user_directory_policy.default_profile
+            );
+            settings.write_to_file(&app_directories.settings_file_path)
+        }
+    }
+}
+
+fn list(settings: &AppSettings, app_directories: &AppDirectories) -> Result<(), Error> {
+    if !settings.user_directory_policy.enabled {
+        println!("Launches don't get a user directory, see user_directory_policy in settings.");
+    }
+
+    for asset in settings.installed_games.iter() {
+        if let Some(profile) = settings.user_directory_profile_of(asset) {
+            println!("{}  ->  {}", InstallationId::from(asset), profile);
+        }
+    }
+    for user_directory_path in list_directory(&app_directories.user_directories_path())? {
+        println!("{}", user_directory_path.display());
+    }
+
+    Ok(())
+}
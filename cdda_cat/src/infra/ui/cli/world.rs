@@ -0,0 +1,127 @@
+use anyhow::{anyhow, Error};
+use cdda_cat_lib::installation_manager::AppSettings;
+
+use crate::infra::fs::app_directories::AppDirectories;
+use crate::infra::fs::backup::SAVE_DIRECTORY_NAME;
+use crate::infra::fs::trash::discard;
+use crate::infra::fs::worlds::{clone_world, read_world_mods, rename_world, world_name};
+use crate::infra::processes::find_processes_using;
+
+use super::backup::back_up;
+use super::location::UserDataLocation;
+use super::prompt::confirm;
+
+#[derive(Debug, Clone)]
+pub enum WorldAction {
+    Clone {
+        location: UserDataLocation,
+        world: String,
+        new_name: String,
+        /// Mod list of the clone, the original's when unset.
+        mods: Option<Vec<String>>,
+    },
+    Rename {
+        location: UserDataLocation,
+        world: String,
+        new_name: String,
+    },
+    Delete {
+        location: UserDataLocation,
+        world: String,
+        assume_yes: bool,
+        /// Overrides the `move_to_trash` setting.
+        trash: Option<bool>,
+    },
+}
+
+/// Fails while a game started from `location`, or using it as its user
+/// directory, is still running.
+pub fn ensure_game_not_running(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    location: &UserDataLocation,
+) -> Result<(), Error> {
+    let location_path = location.resolve(settings, app_directories)?;
+    let process_ids = find_processes_using(&location_path)?;
+    if process_ids.is_empty() {
+        return Ok(());
+    }
+
+    Err(anyhow!(
+        "The game is running from {} (process {}), quit it first!",
+        location,
+        process_ids
+            .iter()
+            .map(|process_id| process_id.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    ))
+}
+
+pub fn world(
+    action: WorldAction,
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+) -> Result<(), Error> {
+    match action {
+        WorldAction::Clone {
+            location,
+            world,
+            new_name,
+            mods,
+        } => {
+            ensure_game_not_running(settings, app_directories, &location)?;
+            let save_path = location
+                .resolve(settings, app_directories)?
+                .join(SAVE_DIRECTORY_NAME);
+            let clone_path = clone_world(&save_path, &world, &new_name, mods.as_deref())?;
+            println!(
+                "Cloned {} of {} to {}",
+                world,
+                location,
+                clone_path.display()
+            );
+            if let Ok(mods) = read_world_mods(&clone_path) {
+                println!("{} uses mods: {}", new_name, mods.join(", "));
+            }
+        }
+        WorldAction::Rename {
+            location,
+            world,
+            new_name,
+        } => {
+            ensure_game_not_running(settings, app_directories, &location)?;
+            let save_path = location
+                .resolve(settings, app_directories)?
+                .join(SAVE_DIRECTORY_NAME);
+            world_name(&new_name)?;
+            back_up(settings, app_directories, &location, Some(&world))?;
+            rename_world(&save_path, &world, &new_name)?;
+            println!("Renamed {} of {} to {}", world, location, new_name);
+        }
+        WorldAction::Delete {
+            location,
+            world,
+            assume_yes,
+            trash,
+        } => {
+            ensure_game_not_running(settings, app_directories, &location)?;
+            let world_path = location
+                .resolve(settings, app_directories)?
+                .join(SAVE_DIRECTORY_NAME)
+                .join(world_name(&world)?);
+            if !world_path.is_dir() {
+                return Err(anyhow!("There is no world {} in {}", world, location));
+            }
+            if !assume_yes && !confirm(&format!("Delete {} of {}?", world, location))? {
+                println!("Aborted, nothing was deleted.");
+                return Ok(());
+            }
+            back_up(settings, app_directories, &location, Some(&world))?;
+            discard(&world_path, trash.unwrap_or(settings.move_to_trash))?;
+            println!("Deleted {} of {}", world, location);
+        }
+    }
+
+    Ok(())
+}
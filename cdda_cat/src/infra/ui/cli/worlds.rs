@@ -0,0 +1,78 @@
+use anyhow::Error;
+use cdda_cat_data::entities::WorldUsage;
+use cdda_cat_lib::installation_manager::AppSettings;
+use chrono::{Local, TimeZone};
+use indicatif::HumanBytes;
+
+use crate::infra::fs::app_directories::AppDirectories;
+use crate::infra::fs::backup::SAVE_DIRECTORY_NAME;
+use crate::infra::fs::worlds::{summarize_worlds, WorldSummary};
+
+use super::location::UserDataLocation;
+
+fn format_modified(modified: u64) -> String {
+    Local
+        .timestamp_opt(modified as i64, 0)
+        .single()
+        .map(|modified| modified.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn print_world(world: &WorldSummary, usage: Option<&WorldUsage>) {
+    println!(
+        "{}  ({}, last played {})",
+        world.name,
+        HumanBytes(world.size),
+        format_modified(world.modified)
+    );
+    if let Some(usage) = usage {
+        println!("  Last saved by: {}", usage.installation);
+    }
+    match &world.mods {
+        Some(mods) if mods.is_empty() => println!("  Mods: none"),
+        Some(mods) => println!("  Mods: {}", mods.join(", ")),
+        None => println!("  Mods: unknown, no readable mods.json"),
+    }
+    if !world.options.is_empty() {
+        println!(
+            "  Options: {}",
+            world
+                .options
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+    }
+    if world.characters.is_empty() {
+        println!("  Characters: none");
+    } else {
+        println!("  Characters: {}", world.characters.join(", "));
+    }
+}
+
+/// Prints an overview of the worlds and characters saved in `location`.
+pub fn worlds(
+    settings: &AppSettings,
+    app_directories: &AppDirectories,
+    location: &UserDataLocation,
+) -> Result<(), Error> {
+    let user_data_path = location.resolve(settings, app_directories)?;
+    let save_path = user_data_path.join(SAVE_DIRECTORY_NAME);
+    let worlds = summarize_worlds(&save_path)?;
+    if worlds.is_empty() {
+        println!("{} has no worlds in {}", location, save_path.display());
+        return Ok(());
+    }
+
+    for world in worlds.iter() {
+        print_world(world, settings.world_usage_of(&user_data_path, &world.name));
+    }
+    println!(
+        "{} worlds, {} in total",
+        worlds.len(),
+        HumanBytes(worlds.iter().map(|world| world.size).sum())
+    );
+
+    Ok(())
+}
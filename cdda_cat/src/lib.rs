@@ -1,60 +1,183 @@
-use crate::infra::archive::unpacker::{ArchiveAsset, ArchiveUnpacker};
-use crate::infra::ui::cli::download_archive::download_archive;
+use crate::infra::fs::app_directories::AppDirectories;
+use crate::infra::fs::content_store::ContentStore;
+use crate::infra::fs::user_data::ConflictResolution;
+use crate::infra::ui::cli::backup::{self, BackupAction};
+use crate::infra::ui::cli::cache::{self, CacheAction};
+use crate::infra::ui::cli::favorite;
+use crate::infra::ui::cli::game_config::{self, GameConfigAction};
+use crate::infra::ui::cli::gc;
+use crate::infra::ui::cli::install::{self, InstallOptions};
+use crate::infra::ui::cli::keybindings::{self, KeybindingsAction};
+use crate::infra::ui::cli::launch::launch_installation;
+use crate::infra::ui::cli::location::UserDataLocation;
+use crate::infra::ui::cli::migrate::{self, MigrateOptions};
+use crate::infra::ui::cli::prune;
+use crate::infra::ui::cli::selection::{EditionFilter, InstallationFilter, OlderThan};
+use crate::infra::ui::cli::subscription;
+use crate::infra::ui::cli::uninstall::{self, RemovalOptions};
+use crate::infra::ui::cli::update;
+use crate::infra::ui::cli::userdir::{self, UserDirectoryAction};
+use crate::infra::ui::cli::world::{self, WorldAction};
+use crate::infra::ui::cli::worlds;
 use anyhow::{anyhow, Context, Error};
-use app_dirs2::{app_root, AppDataType, AppInfo};
-use bpaf::{construct, long, OptionParser, Parser};
+use bpaf::{construct, long, pure, OptionParser, Parser};
 use cdda_cat_data::entities::*;
 use cdda_cat_lib::github_client::GithubClient;
 use cdda_cat_lib::installation_manager::{AppSettings, CDDARelease};
-use std::fs::{self, create_dir_all};
+use indicatif::HumanBytes;
+use std::fs::create_dir_all;
 use std::path::Path;
-use std::process;
 pub mod infra;
 
 fn create_settings_file_unless_exists(settings_filepath: &Path) -> Result<(), Error> {
     if !Path::new(settings_filepath).exists() {
+        if let Some(settings_directory_path) = settings_filepath.parent() {
+            create_dir_all(settings_directory_path)?;
+        }
         AppSettings::default().write_to_file(settings_filepath)?;
     }
 
     Ok(())
 }
 
+#[derive(Debug, Clone)]
+struct Cli {
+    dry_run: bool,
+    json: bool,
+    options: Options,
+}
+
 #[derive(Debug, Clone)]
 enum Options {
     Launch {
-        release_tag: String,
+        release_tag: Option<String>,
         edition: Edition,
+        no_userdir: bool,
+        allow_downgrade: bool,
+        copy_user_data: bool,
     },
     Install {
         release_tag: String,
         download_only: bool,
         overwrite: bool,
+        stream: bool,
+        keep_archive: bool,
         edition: Option<Edition>,
     },
-    Uninstall {
+    Update {
+        edition: Edition,
+        channel: Option<Channel>,
+        remove_old: bool,
+        stream: bool,
+    },
+    Subscribe {
+        edition: Edition,
+        channel: Channel,
+    },
+    Pin {
+        edition: Edition,
+        release_tag: Option<String>,
+    },
+    Unpin {
+        edition: Edition,
+    },
+    Prune {
+        keep_newest: Option<usize>,
+        assume_yes: bool,
+        trash: Option<bool>,
+    },
+    Favorite {
+        release_tag: String,
+        edition: Edition,
+        remove: bool,
+    },
+    Default {
         release_tag: String,
         edition: Edition,
+    },
+    Uninstall {
+        release_tag: Option<String>,
+        edition: EditionFilter,
+        older_than: Option<OlderThan>,
+        all_except_latest: bool,
+        assume_yes: bool,
         remove_cdda_dir_only: bool,
+        purge: bool,
+        trash: Option<bool>,
+    },
+    Migrate {
+        from: UserDataLocation,
+        to: UserDataLocation,
+        move_entries: bool,
+        on_conflict: Option<ConflictResolution>,
+    },
+    Userdir {
+        action: UserDirectoryAction,
+    },
+    Backup {
+        action: BackupAction,
+    },
+    Worlds {
+        location: UserDataLocation,
+    },
+    World {
+        action: WorldAction,
+    },
+    GameConfig {
+        action: GameConfigAction,
+    },
+    Keybindings {
+        action: KeybindingsAction,
+    },
+    Cache {
+        action: CacheAction,
+    },
+    Gc {
+        assume_yes: bool,
+        trash: Option<bool>,
     },
 }
 
 fn launch() -> impl Parser<Options> {
-    let release_tag = long("tag").help("Release tag").argument("TAG");
+    let release_tag = long("tag")
+        .help("Release tag, defaults to the default installation")
+        .argument("TAG")
+        .optional();
     let edition = long("edition")
         .help("Edition")
         .argument("EDITION")
         .fallback(Edition::default())
         .display_fallback();
+    let no_userdir = long("no_userdir")
+        .help("Let the game keep its saves and configuration inside the installation")
+        .switch();
+    let allow_downgrade = long("allow_downgrade")
+        .help("Launch even if a world was last saved by a newer build")
+        .switch();
+    let copy_user_data = long("copy_user_data")
+        .help("Copy saves and configuration inside the installation into its user directory without asking")
+        .switch();
     construct!(Options::Launch {
         release_tag,
-        edition
+        edition,
+        no_userdir,
+        allow_downgrade,
+        copy_user_data
     })
 }
 
 fn install() -> impl Parser<Options> {
-    let release_tag = long("tag").help("Release tag").argument("TAG");
+    let release_tag = long("tag")
+        .help("Release tag, or latest for the newest release of the subscribed channel")
+        .argument("TAG");
     let download_only = long("download_only").help("Download only").switch();
     let overwrite = long("overwrite").help("Overwrite").switch();
+    let stream = long("stream")
+        .help("Unpack while downloading instead of downloading the archive first")
+        .switch();
+    let keep_archive = long("keep_archive")
+        .help("Keep a copy of the archive when unpacking while downloading")
+        .switch();
     let edition = long("edition")
         .help("Edition")
         .argument("EDITION")
@@ -65,199 +188,917 @@ fn install() -> impl Parser<Options> {
         release_tag,
         download_only,
         overwrite,
+        stream,
+        keep_archive,
         edition
     })
 }
 
-fn uninstall() -> impl Parser<Options> {
-    let release_tag = long("tag").help("Tag name").argument("TAG");
+fn update() -> impl Parser<Options> {
+    let edition = long("edition")
+        .help("Edition")
+        .argument("EDITION")
+        .fallback(Edition::default())
+        .display_fallback();
+    let channel = long("channel")
+        .help("experimental or stable, defaults to the subscribed channel")
+        .argument("CHANNEL")
+        .optional();
+    let remove_old = long("remove_old")
+        .help("Launch the new build and uninstall the old one if the game exits cleanly")
+        .switch();
+    let stream = long("stream")
+        .help("Unpack while downloading instead of downloading the archive first")
+        .switch();
+
+    construct!(Options::Update {
+        edition,
+        channel,
+        remove_old,
+        stream
+    })
+}
+
+fn subscribe() -> impl Parser<Options> {
+    let edition = long("edition")
+        .help("Edition")
+        .argument("EDITION")
+        .fallback(Edition::default())
+        .display_fallback();
+    let channel = long("channel")
+        .help("experimental or stable")
+        .argument("CHANNEL");
+
+    construct!(Options::Subscribe { edition, channel })
+}
+
+fn pin() -> impl Parser<Options> {
+    let edition = long("edition")
+        .help("Edition")
+        .argument("EDITION")
+        .fallback(Edition::default())
+        .display_fallback();
+    let release_tag = long("tag")
+        .help("Release tag, defaults to the newest installed one of the subscribed channel")
+        .argument("TAG")
+        .optional();
+
+    construct!(Options::Pin {
+        edition,
+        release_tag
+    })
+}
+
+fn unpin() -> impl Parser<Options> {
+    let edition = long("edition")
+        .help("Edition")
+        .argument("EDITION")
+        .fallback(Edition::default())
+        .display_fallback();
+
+    construct!(Options::Unpin { edition })
+}
+
+fn prune() -> impl Parser<Options> {
+    let keep_newest = long("keep")
+        .help("Keep only this many of the newest installations per edition and channel")
+        .argument("COUNT")
+        .optional();
+    let assume_yes = long("yes")
+        .help("Don't ask before removing the installations")
+        .switch();
+    let trash = trash();
+
+    construct!(Options::Prune {
+        keep_newest,
+        assume_yes,
+        trash
+    })
+}
+
+fn favorite() -> impl Parser<Options> {
+    let release_tag = long("tag").help("Release tag").argument("TAG");
     let edition = long("edition")
         .help("Edition")
         .argument("EDITION")
         .fallback(Edition::default())
         .display_fallback();
+    let remove = long("remove").help("Unmark instead").switch();
+
+    construct!(Options::Favorite {
+        release_tag,
+        edition,
+        remove
+    })
+}
+
+fn default() -> impl Parser<Options> {
+    let release_tag = long("tag").help("Release tag").argument("TAG");
+    let edition = long("edition")
+        .help("Edition")
+        .argument("EDITION")
+        .fallback(Edition::default())
+        .display_fallback();
+
+    construct!(Options::Default {
+        release_tag,
+        edition
+    })
+}
+
+/// Overrides the `move_to_trash` setting.
+fn trash() -> impl Parser<Option<bool>> {
+    let trash = long("trash")
+        .help("Move removed directories to the trash")
+        .req_flag(true);
+    let no_trash = long("no_trash")
+        .help("Delete removed directories right away")
+        .req_flag(false);
+
+    construct!([trash, no_trash]).optional()
+}
+
+fn gc() -> impl Parser<Options> {
+    let assume_yes = long("yes")
+        .help("Don't ask before removing the leftovers")
+        .switch();
+    let trash = trash();
+
+    construct!(Options::Gc { assume_yes, trash })
+}
+
+fn uninstall() -> impl Parser<Options> {
+    let release_tag = long("tag")
+        .help("Tag name, or a glob pattern like 'cdda-experimental-2024-*'")
+        .argument("TAG")
+        .optional();
+    let edition = long("edition")
+        .help("Edition, or any")
+        .argument("EDITION")
+        .fallback(EditionFilter::Only(Edition::default()))
+        .display_fallback();
+    let older_than = long("older-than")
+        .help("Only installations built or installed before a YYYY-MM-DD date, or older than a tag of the same channel")
+        .argument("DATE|TAG")
+        .optional();
+    let all_except_latest = long("all-except-latest")
+        .help("Leave out the newest installation of each edition and channel")
+        .switch();
+    let assume_yes = long("yes")
+        .help("Don't ask before uninstalling several installations")
+        .switch();
     let remove_cdda_dir_only = long("remove_cdda_dir_only").help("REMOVE DIR").switch();
+    let purge = long("purge")
+        .help("Also remove saves and configuration instead of archiving them first")
+        .switch();
+    let trash = trash();
 
     construct!(Options::Uninstall {
         release_tag,
         edition,
+        older_than,
+        all_except_latest,
+        assume_yes,
         remove_cdda_dir_only,
+        purge,
+        trash,
     })
 }
 
-fn options() -> OptionParser<Options> {
+fn migrate() -> impl Parser<Options> {
+    let from = long("from")
+        .help("Installation to take user data from, as TAG/EDITION or just TAG, or a user directory as userdir:PROFILE")
+        .argument("LOCATION");
+    let to = long("to")
+        .help("Installation to put user data into, as TAG/EDITION or just TAG, or a user directory as userdir:PROFILE")
+        .argument("LOCATION");
+    let move_entries = long("move")
+        .help("Remove migrated entries from the source installation")
+        .switch();
+    let on_conflict = long("on_conflict")
+        .help("What to do with entries the destination already has: overwrite, skip or rename")
+        .argument("ACTION")
+        .optional();
+
+    construct!(Options::Migrate {
+        from,
+        to,
+        move_entries,
+        on_conflict
+    })
+}
+
+fn userdir() -> impl Parser<Options> {
+    let list = pure(UserDirectoryAction::List)
+        .to_options()
+        .descr("List user directories and the installations using them")
+        .command("list");
+    let release_tag = long("tag").help("Release tag").argument("TAG");
+    let edition = long("edition")
+        .help("Edition")
+        .argument("EDITION")
+        .fallback(Edition::default())
+        .display_fallback();
+    let profile = long("profile")
+        .help("Share this user directory profile, defaults to a separate one for the installation")
+        .argument("PROFILE")
+        .optional();
+    let assign = construct!(UserDirectoryAction::Assign {
+        release_tag,
+        edition,
+        profile
+    })
+    .to_options()
+    .descr("Give an installation another user directory")
+    .command("assign");
+    let release_tag = long("tag").help("Release tag").argument("TAG");
+    let edition = long("edition")
+        .help("Edition")
+        .argument("EDITION")
+        .fallback(Edition::default())
+        .display_fallback();
+    let unassign = construct!(UserDirectoryAction::Unassign {
+        release_tag,
+        edition
+    })
+    .to_options()
+    .descr("Let an installation use the default user directory again")
+    .command("unassign");
+    let action = construct!([list, assign, unassign]);
+
+    construct!(Options::Userdir { action })
+}
+
+fn backup() -> impl Parser<Options> {
+    let location = long("from")
+        .help("Installation as TAG/EDITION or just TAG, or a user directory as userdir:PROFILE")
+        .argument("LOCATION");
+    let world = long("world")
+        .help("World to back up, defaults to every world")
+        .argument("WORLD")
+        .optional();
+    let create = construct!(BackupAction::Create { location, world })
+        .to_options()
+        .descr("Archive the worlds of an installation or user directory")
+        .command("create");
+    let location = long("from")
+        .help("Only list backups of this installation or user directory")
+        .argument("LOCATION")
+        .optional();
+    let list = construct!(BackupAction::List { location })
+        .to_options()
+        .descr("List backups")
+        .command("list");
+    let archive_path = long("archive")
+        .help("Backup to restore, as listed by backup list")
+        .argument("PATH");
+    let location = long("to")
+        .help("Installation as TAG/EDITION or just TAG, or a user directory as userdir:PROFILE")
+        .argument("LOCATION");
+    let force = long("force")
+        .help("Overwrite worlds that changed after the backup was made")
+        .switch();
+    let restore = construct!(BackupAction::Restore {
+        archive_path,
+        location,
+        force
+    })
+    .to_options()
+    .descr("Put the worlds of a backup back in place")
+    .command("restore");
+    let location = long("from")
+        .help("Only prune backups of this installation or user directory")
+        .argument("LOCATION")
+        .optional();
+    let keep = long("keep")
+        .help("Keep only this many of the newest backups of each world")
+        .argument("COUNT")
+        .optional();
+    let prune = construct!(BackupAction::Prune { location, keep })
+        .to_options()
+        .descr("Remove backups beyond the given or configured retention count")
+        .command("prune");
+    let action = construct!([create, list, restore, prune]);
+
+    construct!(Options::Backup { action })
+}
+
+fn worlds() -> impl Parser<Options> {
+    let location = long("location")
+        .help("Installation as TAG/EDITION or just TAG, or a user directory as userdir:PROFILE")
+        .argument("LOCATION");
+
+    construct!(Options::Worlds { location })
+}
+
+fn world() -> impl Parser<Options> {
+    let location = long("location")
+        .help("Installation as TAG/EDITION or just TAG, or a user directory as userdir:PROFILE")
+        .argument("LOCATION");
+    let world = long("world").help("World directory name").argument("WORLD");
+    let new_name = long("name").help("Name of the copy").argument("NAME");
+    let mods = long("mods")
+        .help("Comma separated mod ids the copy loads instead of the original's")
+        .argument::<String>("MODS")
+        .map(|mods| {
+            mods.split(',')
+                .map(str::trim)
+                .filter(|mod_id| !mod_id.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .optional();
+    let clone = construct!(WorldAction::Clone {
+        location,
+        world,
+        new_name,
+        mods
+    })
+    .to_options()
+    .descr("Copy a world under a new name")
+    .command("clone");
+    let location = long("location")
+        .help("Installation as TAG/EDITION or just TAG, or a user directory as userdir:PROFILE")
+        .argument("LOCATION");
+    let world = long("world").help("World directory name").argument("WORLD");
+    let new_name = long("name").help("New world name").argument("NAME");
+    let rename = construct!(WorldAction::Rename {
+        location,
+        world,
+        new_name
+    })
+    .to_options()
+    .descr("Rename a world after backing it up")
+    .command("rename");
+    let location = long("location")
+        .help("Installation as TAG/EDITION or just TAG, or a user directory as userdir:PROFILE")
+        .argument("LOCATION");
+    let world = long("world").help("World directory name").argument("WORLD");
+    let assume_yes = long("yes")
+        .help("Don't ask before deleting the world")
+        .switch();
+    let trash = trash();
+    let delete = construct!(WorldAction::Delete {
+        location,
+        world,
+        assume_yes,
+        trash
+    })
+    .to_options()
+    .descr("Delete a world after backing it up")
+    .command("delete");
+    let action = construct!([clone, rename, delete]);
+
+    construct!(Options::World { action })
+}
+
+fn game_config() -> impl Parser<Options> {
+    let location = long("location")
+        .help("Installation as TAG/EDITION or just TAG, or a user directory as userdir:PROFILE")
+        .argument("LOCATION");
+    let name = long("name")
+        .help("Option name, like AUTOSAVE_TURNS")
+        .argument("NAME");
+    let get = construct!(GameConfigAction::Get { location, name })
+        .to_options()
+        .descr("Print the value of an option")
+        .command("get");
+    let location = long("location")
+        .help("Installation as TAG/EDITION or just TAG, or a user directory as userdir:PROFILE")
+        .argument("LOCATION");
+    let name = long("name")
+        .help("Option name, like AUTOSAVE_TURNS")
+        .argument("NAME");
+    let value = long("value").help("New value").argument("VALUE");
+    let set = construct!(GameConfigAction::Set {
+        location,
+        name,
+        value
+    })
+    .to_options()
+    .descr("Change an option the installation knows")
+    .command("set");
+    let location = long("location")
+        .help("Installation as TAG/EDITION or just TAG, or a user directory as userdir:PROFILE")
+        .argument("LOCATION");
+    let list = construct!(GameConfigAction::List { location })
+        .to_options()
+        .descr("List all options and their values")
+        .command("list");
+    let location = long("location")
+        .help("Installation as TAG/EDITION or just TAG, or a user directory as userdir:PROFILE")
+        .argument("LOCATION");
+    let against = long("against")
+        .help("Installation or user directory to compare with")
+        .argument("LOCATION");
+    let diff = construct!(GameConfigAction::Diff { location, against })
+        .to_options()
+        .descr("Show options whose values differ between two locations")
+        .command("diff");
+    let from = long("from")
+        .help("Installation moved away from, as TAG/EDITION or just TAG")
+        .argument("INSTALLATION");
+    let to = long("to")
+        .help("Installation moved to, as TAG/EDITION or just TAG")
+        .argument("INSTALLATION");
+    let carry_over = long("carry_over")
+        .help("Copy the values of the old installation without asking")
+        .switch();
+    let changes = construct!(GameConfigAction::Changes {
+        from,
+        to,
+        carry_over
+    })
+    .to_options()
+    .descr("Show options added, renamed or removed between two installations and carry values over. Options built into the game are compared once both installations were launched")
+    .command("changes");
+    let action = construct!([get, set, list, diff, changes]);
+
+    construct!(Options::GameConfig { action })
+}
+
+fn keybindings() -> impl Parser<Options> {
+    let location = long("location")
+        .help("Installation as TAG/EDITION or just TAG, or a user directory as userdir:PROFILE")
+        .argument("LOCATION");
+    let file_path = long("file")
+        .help("File to write the keybindings to")
+        .argument("FILE");
+    let export = construct!(KeybindingsAction::Export {
+        location,
+        file_path
+    })
+    .to_options()
+    .descr("Write the custom keybindings of an installation or user directory to a file")
+    .command("export");
+    let file_path = long("file")
+        .help("Exported keybindings file")
+        .argument("FILE");
+    let location = long("location")
+        .help("Installation as TAG/EDITION or just TAG, or a user directory as userdir:PROFILE")
+        .argument("LOCATION");
+    let import = construct!(KeybindingsAction::Import {
+        file_path,
+        location
+    })
+    .to_options()
+    .descr("Merge exported keybindings into an installation or user directory")
+    .command("import");
+    let from = long("from")
+        .help("Installation as TAG/EDITION or just TAG, or a user directory as userdir:PROFILE")
+        .argument("LOCATION");
+    let sync = construct!(KeybindingsAction::Sync { from })
+        .to_options()
+        .descr("Merge the keybindings of one location into every other installation")
+        .command("sync");
+    let action = construct!([export, import, sync]);
+
+    construct!(Options::Keybindings { action })
+}
+
+fn cache() -> impl Parser<Options> {
+    let list = pure(CacheAction::List)
+        .to_options()
+        .descr("List downloaded archives")
+        .command("list");
+    let keep_last = long("keep")
+        .help("Keep only this many of the newest archives")
+        .argument("COUNT")
+        .optional();
+    let max_total_size = long("max_size")
+        .help("Remove the oldest archives until the rest fits, like 2G or 500M")
+        .argument("SIZE")
+        .optional();
+    let prune = construct!(CacheAction::Prune {
+        keep_last,
+        max_total_size
+    })
+    .to_options()
+    .descr("Remove archives beyond the given or configured retention limits")
+    .command("prune");
+    let assume_yes = long("yes")
+        .help("Don't ask before removing the archives")
+        .switch();
+    let clear = construct!(CacheAction::Clear { assume_yes })
+        .to_options()
+        .descr("Remove every downloaded archive")
+        .command("clear");
+    let action = construct!([list, prune, clear]);
+
+    construct!(Options::Cache { action })
+}
+
+fn options() -> OptionParser<Cli> {
     let launch = launch()
         .to_options()
         .descr("Launch a game")
         .command("launch");
     let install = install().to_options().descr("Install").command("install");
+    let update = update()
+        .to_options()
+        .descr("Update the newest installation of an edition to the latest release")
+        .command("update");
+    let subscribe = subscribe()
+        .to_options()
+        .descr("Choose the release channel an edition follows")
+        .command("subscribe");
+    let pin = pin()
+        .to_options()
+        .descr("Keep an edition on one release")
+        .command("pin");
+    let unpin = unpin()
+        .to_options()
+        .descr("Let a pinned edition follow its channel again")
+        .command("unpin");
+    let prune = prune()
+        .to_options()
+        .descr("Uninstall all but the newest installations of each edition and channel")
+        .command("prune");
+    let favorite = favorite()
+        .to_options()
+        .descr("Mark an installation so prune keeps it")
+        .command("favorite");
+    let default = default()
+        .to_options()
+        .descr("Choose the installation launch runs without --tag")
+        .command("default");
     let uninstall = uninstall()
         .to_options()
         .descr("Uninstall")
         .command("uninstall");
+    let migrate = migrate()
+        .to_options()
+        .descr("Copy or move saves, configuration and added mods between installations and user directories")
+        .command("migrate");
 
-    construct!([launch, install, uninstall]).to_options()
+    let userdir = userdir()
+        .to_options()
+        .descr("Manage user directories that keep saves and configuration across installations")
+        .command("userdir");
+    let backup = backup()
+        .to_options()
+        .descr("Back up and restore worlds")
+        .command("backup");
+    let worlds = worlds()
+        .to_options()
+        .descr("List saved worlds with their mods, options and characters")
+        .command("worlds");
+    let world = world()
+        .to_options()
+        .descr("Clone, rename or delete a saved world")
+        .command("world");
+    let game_config = game_config()
+        .to_options()
+        .descr("Read and change the game's options.json")
+        .command("game-config");
+    let keybindings = keybindings()
+        .to_options()
+        .descr("Export, import and sync custom keybindings")
+        .command("keybindings");
+    let cache = cache()
+        .to_options()
+        .descr("Manage downloaded archives")
+        .command("cache");
+    let gc = gc()
+        .to_options()
+        .descr("Remove unfinished downloads and installation leftovers")
+        .command("gc");
+
+    let dry_run = long("dry-run")
+        .help("Print what install, uninstall, prune or gc would change without changing anything")
+        .switch();
+    let json = long("json")
+        .help("Print the --dry-run plan as JSON")
+        .switch();
+    let options = construct!([
+        launch,
+        install,
+        update,
+        subscribe,
+        pin,
+        unpin,
+        prune,
+        favorite,
+        default,
+        uninstall,
+        migrate,
+        userdir,
+        backup,
+        worlds,
+        world,
+        game_config,
+        keybindings,
+        cache,
+        gc
+    ]);
+
+    construct!(Cli {
+        dry_run,
+        json,
+        options
+    })
+    .to_options()
+}
+
+/// Drops stored files that no installation links to anymore, if
+/// installations are deduplicated at all.
+fn release_unused_content(
+    settings: &AppSettings,
+    content_store: &ContentStore,
+) -> Result<(), Error> {
+    if !settings.deduplicate_installations {
+        return Ok(());
+    }
+    let (removed_files, freed) = content_store.collect_garbage()?;
+    if removed_files > 0 {
+        println!(
+            "Released {} files no installation uses anymore, freeing {}",
+            removed_files,
+            HumanBytes(freed)
+        );
+    }
+
+    Ok(())
 }
-const APP_INFO: AppInfo = AppInfo {
-    name: "cdda_cat",
-    author: "cloudyluna",
-};
 
 pub async fn run() -> anyhow::Result<()> {
-    let system_config_path = app_root(AppDataType::UserConfig, &APP_INFO)?;
-    let system_cache_path = app_root(AppDataType::UserCache, &APP_INFO)?;
-    let settings_file_path = system_config_path
-        .join("settings.json")
-        .as_path()
-        .to_owned();
-    create_settings_file_unless_exists(&settings_file_path)?;
-    let mut settings = AppSettings::default().read_from_file(&settings_file_path)?;
+    let app_directories = AppDirectories::new()?;
+    let content_store = app_directories.content_store();
+    let settings_file_path = app_directories.settings_file_path.clone();
+    let Cli {
+        dry_run,
+        json,
+        options,
+    } = options().run();
+    let mut settings = if dry_run && !settings_file_path.exists() {
+        AppSettings::default()
+    } else {
+        create_settings_file_unless_exists(&settings_file_path)?;
+        AppSettings::default().read_from_file(&settings_file_path)?
+    };
 
     let gh_client = GithubClient::new(
         &settings.upstream_repository.owner_name,
         &settings.upstream_repository.repository_name,
     );
 
-    match options().run() {
+    if dry_run
+        && !matches!(
+            options,
+            Options::Install { .. }
+                | Options::Uninstall { .. }
+                | Options::Prune { .. }
+                | Options::Gc { .. }
+        )
+    {
+        return Err(anyhow!(
+            "--dry-run is only supported by install, uninstall, prune and gc"
+        ));
+    }
+
+    match options {
         Options::Launch {
             release_tag,
             edition,
+            no_userdir,
+            allow_downgrade,
+            copy_user_data,
         } => {
+            let installation_id = match release_tag {
+                Some(release_tag) => InstallationId::new(&release_tag, &edition),
+                None => settings.default_installation.clone().with_context(|| {
+                    "No default installation was chosen, pass --tag or run default first!"
+                })?,
+            };
             let asset = settings
                 .installed_games
                 .iter()
-                .find(|asset| {
-                    asset.platform == Platform::Linux
-                        && asset.tag == release_tag
-                        && asset.edition == edition
-                })
+                .find(|asset| asset.platform == Platform::Linux && installation_id.matches(asset))
+                .cloned()
                 .with_context(|| {
                     format!(
                         "No asset with release tag of {} and edition of {} was found to be launched!",
-                        release_tag, edition
+                        installation_id.tag, installation_id.edition
                     )
                 })?;
             println!("Found existing installation!");
-            let launcher_path = asset
-                .game_edition_directory_path
-                .join(settings.decompressed_game_directory_path.as_path())
-                .join(settings.launcher_name.as_str());
-            println!("Running {}", &launcher_path.display());
-            process::Command::new(launcher_path)
-                .spawn()
-                .with_context(|| format!("Failed to launch {}", settings.launcher_name.as_str()))?
-                .wait()?;
+            launch_installation(
+                &mut settings,
+                &app_directories,
+                &asset,
+                !no_userdir,
+                allow_downgrade,
+                copy_user_data,
+            )?;
         }
         Options::Install {
             release_tag,
             download_only,
             overwrite,
+            stream,
+            keep_archive,
             edition,
         } => {
-            let system_download_dir = system_cache_path
-                .join(settings.root_download_directory_path.as_path())
-                .into_os_string()
-                .into_string()
-                .map_err(|_| anyhow!("Cannot convert top download directory path to String"))?;
-            let root_download_directory_path = RootDownloadDirectoryPath::new(&system_download_dir);
-            let release = CDDARelease::fetch_by_tag(gh_client, &release_tag).await?;
-            let mut release_assets = release.assets.iter().map(|asset| Asset {
-                name: asset.name.to_string(),
-                tag: release.tag_name.to_string(),
-                platform: Platform::from(asset.name.as_str()),
-                edition: Edition::from(asset.name.as_str()),
-                url: asset.browser_download_url.to_string(),
-                game_edition_directory_path: GameEditionDirectoryPath::default(),
-            });
-
-            let asset = release_assets
-                .find(|asset: &Asset| {
-                    asset.platform == Platform::Linux
-                        && asset.edition == edition.clone().unwrap_or_default()
-                })
-                .ok_or_else(|| {
-                    anyhow!("Cannot find asset! Maybe try again with different edition?")
-                })?;
-
-            let game_edition_directory_path =
-                root_download_directory_path.to_game_edition_directory_path(&asset);
-            let is_game_directory_exists = game_edition_directory_path
-                .join(settings.decompressed_game_directory_path.as_path())
-                .exists();
-            if is_game_directory_exists && !overwrite {
-                eprintln!(
-                    "{} installation directory already exists and we won't overwrite it!",
-                    settings.decompressed_game_directory_path.display()
-                );
-                eprintln!("Retry with --overwrite flag to force installation directory overwrite.");
-                eprintln!("Aborted for now");
-
-                process::exit(1);
+            let edition = edition.unwrap_or_default();
+            let release = if release_tag == update::LATEST_TAG {
+                update::fetch_latest_release(gh_client, &settings.subscription_of(&edition)).await?
+            } else {
+                CDDARelease::fetch_by_tag(gh_client, &release_tag).await?
+            };
+            let install_options = InstallOptions {
+                download_only,
+                overwrite,
+                stream,
+                keep_archive,
+            };
+            if dry_run {
+                return install::plan_install(
+                    &settings,
+                    &app_directories,
+                    &release,
+                    &edition,
+                    &install_options,
+                )?
+                .print(json);
             }
 
-            create_dir_all(game_edition_directory_path.as_path())?;
-            let archive_path = ArchiveFilePath::new(game_edition_directory_path.join(&asset.name));
-
-            download_archive(&asset.url, &archive_path).await?;
-
+            let asset = install::install(
+                &mut settings,
+                &app_directories,
+                &release,
+                &edition,
+                &install_options,
+            )
+            .await?;
             if !download_only {
-                ArchiveAsset::new(asset).unpack(
-                    &mut settings,
-                    &archive_path,
-                    &game_edition_directory_path,
-                    &settings_file_path,
-                )?;
+                prune::prune_after_install(&mut settings, &app_directories, Some(&asset))?;
+                release_unused_content(&settings, &content_store)?;
             }
             println!("\nDone!")
         }
+        Options::Update {
+            edition,
+            channel,
+            remove_old,
+            stream,
+        } => {
+            let is_updated = update::update(
+                &mut settings,
+                &app_directories,
+                gh_client,
+                &edition,
+                channel,
+                remove_old,
+                &InstallOptions {
+                    stream,
+                    ..InstallOptions::default()
+                },
+            )
+            .await?;
+            if is_updated {
+                prune::prune_after_install(&mut settings, &app_directories, None)?;
+                release_unused_content(&settings, &content_store)?;
+                println!("\nDone!")
+            }
+        }
+        Options::Subscribe { edition, channel } => {
+            subscription::subscribe(&mut settings, &settings_file_path, &edition, channel)?
+        }
+        Options::Pin {
+            edition,
+            release_tag,
+        } => subscription::pin(&mut settings, &settings_file_path, &edition, release_tag)?,
+        Options::Unpin { edition } => {
+            subscription::unpin(&mut settings, &settings_file_path, &edition)?
+        }
+        Options::Prune {
+            keep_newest,
+            assume_yes,
+            trash,
+        } => {
+            let keep_newest = prune::resolve_keep_newest(&settings, keep_newest)?;
+            let removal_options = RemovalOptions {
+                use_trash: trash.unwrap_or(settings.move_to_trash),
+                ..RemovalOptions::default()
+            };
+            if dry_run {
+                return prune::plan_prune(
+                    &settings,
+                    &app_directories,
+                    keep_newest,
+                    None,
+                    &removal_options,
+                )?
+                .print(json);
+            }
+            prune::prune_installations(
+                &mut settings,
+                &app_directories,
+                keep_newest,
+                None,
+                &removal_options,
+                assume_yes,
+            )?;
+            release_unused_content(&settings, &content_store)?;
+        }
+        Options::Favorite {
+            release_tag,
+            edition,
+            remove,
+        } => favorite::set_favorite(
+            &mut settings,
+            &settings_file_path,
+            InstallationId::new(&release_tag, &edition),
+            !remove,
+        )?,
+        Options::Default {
+            release_tag,
+            edition,
+        } => favorite::set_default(
+            &mut settings,
+            &settings_file_path,
+            InstallationId::new(&release_tag, &edition),
+        )?,
         Options::Uninstall {
             release_tag,
             edition,
+            older_than,
+            all_except_latest,
+            assume_yes,
             remove_cdda_dir_only,
+            purge,
+            trash,
         } => {
-            let asset = settings
-                .installed_games
-                .iter()
-                // TODO: Check OS and match platform type.
-                .find(|asset| asset.tag == release_tag && asset.edition == edition)
-                .with_context(|| {
-                    format!(
-                        "No asset with release tag of {} and edition of {} was found to uninstall!",
-                        release_tag, edition
-                    )
-                })?;
+            let filter = InstallationFilter {
+                tag_pattern: release_tag,
+                edition,
+                older_than,
+                all_except_latest,
+            };
+            let removal_options = RemovalOptions {
+                remove_cdda_dir_only,
+                purge,
+                use_trash: trash.unwrap_or(settings.move_to_trash),
+            };
+            if dry_run {
+                return uninstall::plan_uninstall(
+                    &settings,
+                    &app_directories,
+                    &filter,
+                    &removal_options,
+                )?
+                .print(json);
+            }
 
-            println!("Found existing installation!");
-            println!("Uninstalling..");
-            let game_edition_directory_path = &asset.game_edition_directory_path;
-            let decompressed_game_directory_path = &game_edition_directory_path
-                .as_path()
-                .join(settings.decompressed_game_directory_path.as_path());
-
-            if remove_cdda_dir_only {
-                fs::remove_dir_all(decompressed_game_directory_path).with_context(|| {
-                    format!(
-                        "Failed to remove CDDA's installation directory at: {}",
-                        decompressed_game_directory_path.display()
-                    )
-                })?;
-            } else {
-                fs::remove_dir_all(game_edition_directory_path.as_path()).with_context(|| {
-                    format!(
-                        "Failed to remove installation directory path of: {}",
-                        &game_edition_directory_path.display()
-                    )
-                })?;
+            let removed = uninstall::uninstall(
+                &mut settings,
+                &app_directories,
+                &filter,
+                &removal_options,
+                assume_yes,
+            )?;
+            if removed > 0 {
+                release_unused_content(&settings, &content_store)?;
+                println!("Finished uninstall!");
             }
-            let new_assets: Vec<Asset> = settings
-                .installed_games
-                .iter()
-                .filter(|x| *x != asset)
-                .cloned()
-                .collect();
-            settings.installed_games = ReleaseAssets::new(new_assets);
-            settings.write_to_file(&settings_file_path)?;
-            println!("Finished uninstall!");
+        }
+        Options::Migrate {
+            from,
+            to,
+            move_entries,
+            on_conflict,
+        } => migrate::migrate(
+            &settings,
+            &app_directories,
+            &from,
+            &to,
+            &MigrateOptions {
+                move_entries,
+                on_conflict,
+            },
+        )?,
+        Options::Userdir { action } => userdir::userdir(action, &mut settings, &app_directories)?,
+        Options::Backup { action } => backup::backup(action, &settings, &app_directories)?,
+        Options::Worlds { location } => worlds::worlds(&settings, &app_directories, &location)?,
+        Options::World { action } => world::world(action, &settings, &app_directories)?,
+        Options::GameConfig { action } => {
+            game_config::game_config(action, &settings, &app_directories)?
+        }
+        Options::Keybindings { action } => {
+            keybindings::keybindings(action, &settings, &app_directories)?
+        }
+        Options::Cache { action } => cache::cache(
+            action,
+            &app_directories.root_download_directory_path(&settings)?,
+            &settings.archive_cache_policy,
+        )?,
+        Options::Gc { assume_yes, trash } => {
+            let use_trash = trash.unwrap_or(settings.move_to_trash);
+            if dry_run {
+                return gc::plan_gc(&settings, &app_directories, use_trash)?.print(json);
+            }
+            gc::gc(&settings, &app_directories, use_trash, assume_yes)?;
+            release_unused_content(&settings, &content_store)?;
         }
     }
 